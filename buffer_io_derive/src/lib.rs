@@ -0,0 +1,151 @@
+//! Derive macros for `buffer_io::buffer::BufferSerialize` and `BufferDeserialize`.
+//! Enable the `derive` feature of `buffer_io` rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Index};
+
+/// Writes each field in declaration order. Fieldless enums are written as the variant's
+/// discriminant cast to a u32, so explicit discriminants are kept on the wire.
+#[proc_macro_derive(BufferSerialize)]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Reads each field in declaration order, mirroring `#[derive(BufferSerialize)]`.
+/// Fieldless enums fail with `InvalidTag` when the tag matches no variant's discriminant.
+#[proc_macro_derive(BufferDeserialize)]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_deserialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Requires every type parameter to implement the trait being derived.
+fn add_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// Returns the names of a fieldless enum's variants, or an error pointing at the first
+/// variant that carries data.
+fn unit_variants(data: &syn::DataEnum) -> syn::Result<Vec<&syn::Ident>> {
+    data.variants
+        .iter()
+        .map(|v| match v.fields {
+            Fields::Unit => Ok(&v.ident),
+            _ => Err(syn::Error::new_spanned(
+                v,
+                "only enums without fields can derive buffer traits",
+            )),
+        })
+        .collect()
+}
+
+fn expand_serialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = data.fields.iter().enumerate().map(|(i, field)| {
+                let member = match &field.ident {
+                    Some(ident) => quote!(#ident),
+                    None => {
+                        let index = Index::from(i);
+                        quote!(#index)
+                    }
+                };
+                quote! {
+                    ::buffer_io::buffer::BufferSerialize::write_to(&self.#member, writer)?;
+                }
+            });
+            quote! {
+                #(#fields)*
+                Ok(())
+            }
+        }
+        Data::Enum(data) => {
+            let variants = unit_variants(data)?;
+            quote! {
+                let tag: u32 = match self {
+                    #(#name::#variants => #name::#variants as u32,)*
+                };
+                writer.write_u32(tag).map(|_n| ())
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "unions cannot derive buffer traits",
+            ))
+        }
+    };
+    let generics = add_bounds(
+        input.generics.clone(),
+        quote!(::buffer_io::buffer::BufferSerialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::buffer_io::buffer::BufferSerialize for #name #ty_generics #where_clause {
+            fn write_to<__W: ::buffer_io::io::Write>(
+                &self,
+                writer: &mut ::buffer_io::buffer::BufferWriter<__W>,
+            ) -> ::core::result::Result<(), ::buffer_io::buffer::BufferError> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_deserialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let read = quote!(::buffer_io::buffer::BufferDeserialize::read_from(reader)?);
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let names = fields.named.iter().map(|f| &f.ident);
+                quote!(Ok(#name { #(#names: #read,)* }))
+            }
+            Fields::Unnamed(fields) => {
+                let reads = fields.unnamed.iter().map(|_f| &read);
+                quote!(Ok(#name(#(#reads,)*)))
+            }
+            Fields::Unit => quote!(Ok(#name)),
+        },
+        Data::Enum(data) => {
+            let variants = unit_variants(data)?;
+            quote! {
+                match reader.read_u32()? {
+                    #(tag if tag == #name::#variants as u32 => Ok(#name::#variants),)*
+                    tag => Err(::buffer_io::buffer::BufferError::InvalidTag { tag }),
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "unions cannot derive buffer traits",
+            ))
+        }
+    };
+    let generics = add_bounds(
+        input.generics.clone(),
+        quote!(::buffer_io::buffer::BufferDeserialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::buffer_io::buffer::BufferDeserialize for #name #ty_generics #where_clause {
+            fn read_from<__R: ::buffer_io::io::Read>(
+                reader: &mut ::buffer_io::buffer::BufferReader<__R>,
+            ) -> ::core::result::Result<Self, ::buffer_io::buffer::BufferError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,81 @@
+//! A `#![no_std]` user of buffer_io, built against the crate with only the `alloc` feature.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use buffer_io::buffer::{
+    BufferDeserialize, BufferError, BufferReader, BufferSerialize, BufferWriter,
+};
+use buffer_io::io::Cursor;
+
+#[derive(Debug, PartialEq, BufferSerialize, BufferDeserialize)]
+pub struct Sample {
+    pub id: u32,
+    pub values: Vec<i16>,
+}
+
+/// Writes `value` to an in-memory cursor, then reads it back.
+pub fn round_trip_u32(value: u32) -> Result<u32, BufferError> {
+    let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+    buffer.write_u32(value)?;
+    let mut reader = BufferReader::new(Cursor::new(buffer.to_vec()?));
+    reader.read_u32()
+}
+
+/// Writes a derived struct to an in-memory cursor, then reads it back.
+pub fn round_trip_sample(sample: &Sample) -> Result<Sample, BufferError> {
+    let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+    sample.write_to(&mut buffer)?;
+    let mut reader = BufferReader::new(Cursor::new(buffer.to_vec()?));
+    Sample::read_from(&mut reader)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn round_trips_without_std() {
+        use crate::{round_trip_sample, round_trip_u32, Sample};
+        assert_eq!(9001, round_trip_u32(9001).unwrap());
+        let sample = Sample {
+            id: 7,
+            values: vec![-1, 2, -3],
+        };
+        assert_eq!(sample, round_trip_sample(&sample).unwrap());
+    }
+
+    #[test]
+    fn io_shim_and_float_fallbacks() {
+        use buffer_io::buffer::{BufferError, BufferReader, BufferWriter, CoordinateAxis};
+        use buffer_io::io::Cursor;
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.mark_label("count").unwrap();
+        buffer.write_u32(0).unwrap();
+        buffer.write_vectored(&[b"ab", b"", b"cde"]).unwrap();
+        buffer
+            .write_coord_e7(-33.868_820_05, CoordinateAxis::Latitude)
+            .unwrap();
+        buffer
+            .write_quaternion_compressed([0.5, -0.5, 0.5, 0.5])
+            .unwrap();
+        buffer.patch_label_u32("count", 5).unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(5, reader.read_u32().unwrap());
+        reader.skip(5).unwrap();
+        assert_eq!(
+            (-33.868_820_05f64 * 1e7).round() / 1e7,
+            reader.read_coord_e7(CoordinateAxis::Latitude).unwrap()
+        );
+        let q = reader.read_quaternion_compressed().unwrap();
+        for (actual, expected) in q.iter().zip([0.5f32, -0.5, 0.5, 0.5].iter()) {
+            assert!((actual - expected).abs() < 1e-3, "{:?}", q);
+        }
+        assert!(matches!(
+            reader.skip(1),
+            Err(BufferError::EndOfStream { .. })
+        ));
+    }
+}
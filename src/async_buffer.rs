@@ -0,0 +1,357 @@
+use crate::buffer::{
+    check_7bit_int_byte, check_filled, chunk_error, read_step, BufferError, Endianness,
+    STREAM_CHUNK_SIZE,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Writes primitive types as binary values to an async stream, laid out the same way as
+/// `BufferWriter` so either side of a connection can use the sync or async API.
+pub struct AsyncBufferWriter<W: AsyncWrite + Unpin> {
+    writer: W,
+    endianness: Endianness,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncBufferWriter<W> {
+    /// Creates a new AsyncBufferWriter that writes little-endian integers
+    pub fn new(writer: W) -> Self {
+        AsyncBufferWriter::with_endianness(writer, Endianness::Little)
+    }
+
+    /// Creates a new AsyncBufferWriter that writes integers in the given byte order
+    pub fn with_endianness(writer: W, endianness: Endianness) -> Self {
+        AsyncBufferWriter { writer, endianness }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the AsyncBufferWriter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes all of `data`, retrying short writes, and returns its length.
+    async fn write_raw(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+        self.writer
+            .write_all(data)
+            .await
+            .map_err(|_e| BufferError::IOFailure)?;
+        Ok(data.len() as u64)
+    }
+
+    /// Flushes the underlying writer, so buffered sinks send what has been written so far.
+    pub async fn flush(&mut self) -> Result<(), BufferError> {
+        self.writer
+            .flush()
+            .await
+            .map_err(|_e| BufferError::IOFailure)
+    }
+
+    /// Writes an unsigned byte to the stream.
+    pub async fn write_u8(&mut self, value: u8) -> Result<u64, BufferError> {
+        self.write_raw(&[value]).await
+    }
+
+    /// Writes a signed byte to the stream.
+    pub async fn write_i8(&mut self, value: i8) -> Result<u64, BufferError> {
+        self.write_u8(value as u8).await
+    }
+
+    /// Writes a two-byte unsigned integer to the stream.
+    pub async fn write_u16(&mut self, value: u16) -> Result<u64, BufferError> {
+        let data = match self.endianness {
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        };
+        self.write_raw(&data).await
+    }
+
+    /// Writes a two-byte signed integer to the stream.
+    pub async fn write_i16(&mut self, value: i16) -> Result<u64, BufferError> {
+        self.write_u16(value as u16).await
+    }
+
+    /// Writes a four-byte unsigned integer to the stream.
+    pub async fn write_u32(&mut self, value: u32) -> Result<u64, BufferError> {
+        let data = match self.endianness {
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        };
+        self.write_raw(&data).await
+    }
+
+    /// Writes a four-byte signed integer to the stream.
+    pub async fn write_i32(&mut self, value: i32) -> Result<u64, BufferError> {
+        self.write_u32(value as u32).await
+    }
+
+    /// Writes an eight-byte unsigned integer to the stream.
+    pub async fn write_u64(&mut self, value: u64) -> Result<u64, BufferError> {
+        let data = match self.endianness {
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        };
+        self.write_raw(&data).await
+    }
+
+    /// Writes an eight-byte signed integer to the stream.
+    pub async fn write_i64(&mut self, value: i64) -> Result<u64, BufferError> {
+        self.write_u64(value as u64).await
+    }
+
+    /// Writes a four-byte IEEE-754 float to the stream, preserving its exact bit pattern.
+    pub async fn write_f32(&mut self, value: f32) -> Result<u64, BufferError> {
+        self.write_u32(value.to_bits()).await
+    }
+
+    /// Writes an eight-byte IEEE-754 float to the stream, preserving its exact bit pattern.
+    pub async fn write_f64(&mut self, value: f64) -> Result<u64, BufferError> {
+        self.write_u64(value.to_bits()).await
+    }
+
+    /// Writes a boolean as a single byte, 1 for true and 0 for false.
+    pub async fn write_bool(&mut self, value: bool) -> Result<u64, BufferError> {
+        self.write_u8(value as u8).await
+    }
+
+    /// Writes the bytes as they are, without a length prefix.
+    pub async fn write_bytes(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+        self.write_raw(data).await
+    }
+
+    /// Write out an int 7 bits at a time, the same way as `BufferWriter::write_7bit_int`.
+    pub async fn write_7bit_int(&mut self, value: i32) -> Result<(), BufferError> {
+        let mut v = value as u32;
+        while v >= 0x80 {
+            self.write_u8((v | 0x80) as u8).await?;
+            v >>= 7;
+        }
+        self.write_u8(v as u8).await?;
+        Ok(())
+    }
+
+    /// Writes a UTF-8 string prefixed by its 7-bit encoded length in bytes.
+    pub async fn write_string(&mut self, value: &str) -> Result<u64, BufferError> {
+        if value.len() > i32::MAX as usize {
+            return Err(BufferError::StringTooLong {
+                length: value.len(),
+                max: i32::MAX as usize,
+            });
+        }
+        self.write_7bit_int(value.len() as i32).await?;
+        self.write_raw(value.as_bytes()).await
+    }
+}
+
+/// Reads primitive types written by `BufferWriter` or `AsyncBufferWriter` from an async stream.
+pub struct AsyncBufferReader<R: AsyncRead + Unpin> {
+    reader: R,
+    endianness: Endianness,
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufferReader<R> {
+    /// Creates a new AsyncBufferReader that reads little-endian integers
+    pub fn new(reader: R) -> Self {
+        AsyncBufferReader::with_endianness(reader, Endianness::Little)
+    }
+
+    /// Creates a new AsyncBufferReader that reads integers in the given byte order
+    pub fn with_endianness(reader: R, endianness: Endianness) -> Self {
+        AsyncBufferReader { reader, endianness }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Consumes the AsyncBufferReader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Fills the buffer from the stream, failing with `EndOfStream` if it ends first.
+    async fn fill(&mut self, buffer: &mut [u8]) -> Result<(), BufferError> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            if read_step(self.reader.read(&mut buffer[filled..]).await, &mut filled)? {
+                break;
+            }
+        }
+        check_filled(buffer.len(), filled)
+    }
+
+    /// Reads the next byte from the stream.
+    pub async fn read_u8(&mut self) -> Result<u8, BufferError> {
+        let mut buffer = [0u8; 1];
+        self.fill(&mut buffer).await.map(|_b| buffer[0])
+    }
+
+    /// Reads a signed byte from the stream.
+    pub async fn read_i8(&mut self) -> Result<i8, BufferError> {
+        self.read_u8().await.map(|b| b as i8)
+    }
+
+    /// Reads a 2-byte unsigned integer from the stream.
+    pub async fn read_u16(&mut self) -> Result<u16, BufferError> {
+        let mut buffer = [0u8; 2];
+        self.fill(&mut buffer).await?;
+        Ok(match self.endianness {
+            Endianness::Little => u16::from_le_bytes(buffer),
+            Endianness::Big => u16::from_be_bytes(buffer),
+        })
+    }
+
+    /// Reads a 2-byte signed integer from the stream.
+    pub async fn read_i16(&mut self) -> Result<i16, BufferError> {
+        self.read_u16().await.map(|v| v as i16)
+    }
+
+    /// Reads a 4-byte unsigned integer from the stream.
+    pub async fn read_u32(&mut self) -> Result<u32, BufferError> {
+        let mut buffer = [0u8; 4];
+        self.fill(&mut buffer).await?;
+        Ok(match self.endianness {
+            Endianness::Little => u32::from_le_bytes(buffer),
+            Endianness::Big => u32::from_be_bytes(buffer),
+        })
+    }
+
+    /// Reads a 4-byte signed integer from the stream.
+    pub async fn read_i32(&mut self) -> Result<i32, BufferError> {
+        self.read_u32().await.map(|v| v as i32)
+    }
+
+    /// Reads an 8-byte unsigned integer from the stream.
+    pub async fn read_u64(&mut self) -> Result<u64, BufferError> {
+        let mut buffer = [0u8; 8];
+        self.fill(&mut buffer).await?;
+        Ok(match self.endianness {
+            Endianness::Little => u64::from_le_bytes(buffer),
+            Endianness::Big => u64::from_be_bytes(buffer),
+        })
+    }
+
+    /// Reads an 8-byte signed integer from the stream.
+    pub async fn read_i64(&mut self) -> Result<i64, BufferError> {
+        self.read_u64().await.map(|v| v as i64)
+    }
+
+    /// Reads a 4-byte IEEE-754 float from the stream.
+    pub async fn read_f32(&mut self) -> Result<f32, BufferError> {
+        self.read_u32().await.map(f32::from_bits)
+    }
+
+    /// Reads an 8-byte IEEE-754 float from the stream.
+    pub async fn read_f64(&mut self) -> Result<f64, BufferError> {
+        self.read_u64().await.map(f64::from_bits)
+    }
+
+    /// Reads a boolean stored as a single byte, where any nonzero byte is true.
+    pub async fn read_bool(&mut self) -> Result<bool, BufferError> {
+        self.read_u8().await.map(|b| b != 0)
+    }
+
+    /// Reads the specified number of bytes from the stream. The array grows as bytes arrive,
+    /// so a count beyond the end of the stream fails without first allocating the whole count.
+    pub async fn read_bytes(&mut self, count: u64) -> Result<Vec<u8>, BufferError> {
+        let mut buffer = Vec::new();
+        let mut remaining = count;
+        while remaining > 0 {
+            let start = buffer.len();
+            let chunk = remaining.min(STREAM_CHUNK_SIZE as u64) as usize;
+            buffer.resize(start + chunk, 0);
+            self.fill(&mut buffer[start..])
+                .await
+                .map_err(|e| chunk_error(e, count, start))?;
+            remaining -= chunk as u64;
+        }
+        Ok(buffer)
+    }
+
+    /// Reads in a 32-bit integer in compressed format, with the same limits as
+    /// `BufferReader::read_7bit_int`.
+    pub async fn read_7bit_int(&mut self) -> Result<i32, BufferError> {
+        let mut count: i32 = 0;
+        let mut shift = 0;
+        loop {
+            if shift == 5 * 7 {
+                return Err(BufferError::InvalidData {
+                    message: "7-bit encoded i32 is longer than 5 bytes".to_string(),
+                });
+            }
+            let b = self.read_u8().await?;
+            check_7bit_int_byte(shift, b)?;
+            count |= ((b & 0x7F) as i32) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Reads a string written by `write_string`.
+    pub async fn read_string(&mut self) -> Result<String, BufferError> {
+        let length = self.read_7bit_int().await? as u64;
+        let bytes = self.read_bytes(length).await?;
+        String::from_utf8(bytes).map_err(|_e| BufferError::IOFailure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn round_trips_through_a_pipe() {
+        use crate::async_buffer::{AsyncBufferReader, AsyncBufferWriter};
+        use crate::buffer::{BufferError, BufferReader, Endianness};
+        use std::io::Cursor;
+        let (client, server) = tokio::io::duplex(16);
+        let writing = async move {
+            let mut writer = AsyncBufferWriter::with_endianness(client, Endianness::Big);
+            writer.write_u32(9001).await.unwrap();
+            writer.write_i64(-2).await.unwrap();
+            writer.write_f64(0.5).await.unwrap();
+            writer.write_bool(true).await.unwrap();
+            writer.write_string("Hello World!").await.unwrap();
+            writer.flush().await.unwrap();
+        };
+        let reading = async move {
+            let mut reader = AsyncBufferReader::with_endianness(server, Endianness::Big);
+            assert_eq!(9001, reader.read_u32().await.unwrap());
+            assert_eq!(-2, reader.read_i64().await.unwrap());
+            assert_eq!(0.5, reader.read_f64().await.unwrap());
+            assert!(reader.read_bool().await.unwrap());
+            assert_eq!("Hello World!", reader.read_string().await.unwrap());
+            // The writer has been dropped, closing its end of the pipe.
+            assert_eq!(
+                Err(BufferError::EndOfStream {
+                    needed: 1,
+                    available: 0
+                }),
+                reader.read_u8().await
+            );
+        };
+        tokio::join!(writing, reading);
+
+        // The async writer lays data out the same way as the sync one.
+        let mut writer = AsyncBufferWriter::new(Vec::new());
+        writer.write_u16(7).await.unwrap();
+        writer.write_string("sync").await.unwrap();
+        let mut reader = BufferReader::new(Cursor::new(writer.into_inner()));
+        assert_eq!(7, reader.read_u16().unwrap());
+        assert_eq!("sync", reader.read_string().unwrap());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::buffer::{BufferError, BufferReader};
+use bytes::Bytes;
+use std::io::Cursor;
+
+/// `Bytes` is `AsRef<[u8]>`, so `Cursor<Bytes>` is already a seekable reader and can be
+/// decoded without copying the buffer.
+impl BufferReader<Cursor<Bytes>> {
+    /// Creates a new BufferReader over a `bytes::Bytes` buffer without copying it.
+    pub fn from_bytes_crate(buf: Bytes) -> Self {
+        BufferReader::new(Cursor::new(buf))
+    }
+
+    /// Reads the specified number of bytes as a `Bytes` slice sharing the original buffer
+    /// and advances the current position by that number of bytes.
+    pub fn read_bytes_zero_copy(&mut self, count: u64) -> Result<Bytes, BufferError> {
+        let start = self.position()?;
+        let available = self.len()?.saturating_sub(start);
+        if count > available {
+            return Err(BufferError::EndOfStream {
+                needed: count,
+                available,
+            });
+        }
+        let end = start + count;
+        let slice = self.get_ref().get_ref().slice(start as usize..end as usize);
+        self.seek(end as i64, crate::buffer::SeekOrigin::Begin)?;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn decodes_without_copying() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use bytes::Bytes;
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.write_bytes(&[0xAB; 64]).unwrap();
+        let buf = Bytes::from(buffer.to_vec().unwrap());
+        let range = buf.as_ptr_range();
+
+        let mut reader = BufferReader::from_bytes_crate(buf.clone());
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+        let slice = reader.read_bytes_zero_copy(64).unwrap();
+        assert_eq!(&[0xAB; 64][..], &slice[..]);
+        assert!(range.contains(&slice.as_ptr()));
+        assert!(reader.read_bytes_zero_copy(1).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::buffer::STREAM_CHUNK_SIZE;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Presents a non-seekable decompressor as a seekable stream, for the `BufferReader` methods
+/// that need to seek or know the stream length.
+/// Decompressed bytes are pulled from the inner stream only as far as reads and seeks require,
+/// and are kept in memory so earlier positions can be revisited. Seeking relative to the end,
+/// which `BufferReader::len` does, decompresses the remainder of the stream.
+pub struct DecompressReader<R: Read> {
+    inner: R,
+    buffer: Cursor<Vec<u8>>,
+    finished: bool,
+}
+
+impl<R: Read> DecompressReader<R> {
+    /// Creates a new DecompressReader over a decompressing stream
+    pub fn new(inner: R) -> Self {
+        DecompressReader {
+            inner,
+            buffer: Cursor::new(Vec::new()),
+            finished: false,
+        }
+    }
+
+    /// Decompresses until at least `target` bytes are buffered or the inner stream ends.
+    fn fill_to(&mut self, target: u64) -> io::Result<()> {
+        let mut chunk = [0u8; STREAM_CHUNK_SIZE];
+        while !self.finished && (self.buffer.get_ref().len() as u64) < target {
+            match self.inner.read(&mut chunk) {
+                Ok(0) => self.finished = true,
+                Ok(n) => self.buffer.get_mut().extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let target = self.buffer.position().saturating_add(buf.len() as u64);
+        self.fill_to(target)?;
+        self.buffer.read(buf)
+    }
+}
+
+impl<R: Read> Seek for DecompressReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.buffer.position().checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                self.fill_to(u64::MAX)?;
+                (self.buffer.get_ref().len() as u64).checked_add_signed(offset)
+            }
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.fill_to(target)?;
+        self.buffer.seek(SeekFrom::Start(target))
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: Read> DecompressReader<flate2::read::GzDecoder<R>> {
+    /// Creates a new DecompressReader over a gzip compressed stream
+    pub fn gzip(reader: R) -> Self {
+        DecompressReader::new(flate2::read::GzDecoder::new(reader))
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<R: Read> DecompressReader<zstd::stream::read::Decoder<'static, io::BufReader<R>>> {
+    /// Creates a new DecompressReader over a zstd compressed stream
+    pub fn zstd(reader: R) -> Result<Self, crate::buffer::BufferError> {
+        zstd::stream::read::Decoder::new(reader)
+            .map(DecompressReader::new)
+            .map_err(crate::buffer::BufferError::ReadFailure)
+    }
+}
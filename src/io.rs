@@ -0,0 +1,342 @@
+//! The I/O traits and types that `BufferWriter` and `BufferReader` are generic over.
+//! With the `std` feature these are the `std::io` items themselves. Without it, this is a
+//! minimal stand-in with the same names covering what the buffer types use.
+
+#[cfg(feature = "std")]
+pub use std::io::{Cursor, Error, ErrorKind, IoSlice, Read, Result, Seek, SeekFrom, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::shim::*;
+
+#[cfg(not(feature = "std"))]
+mod shim {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use core::fmt;
+    use core::ops::Deref;
+
+    /// The kind of an I/O error, a subset of `std::io::ErrorKind`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        Interrupted,
+        InvalidInput,
+        UnexpectedEof,
+        WriteZero,
+        Other,
+    }
+
+    /// An I/O error with its kind and a description.
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: String,
+    }
+
+    impl Error {
+        /// Creates a new error of the given kind.
+        pub fn new<M: Into<String>>(kind: ErrorKind, message: M) -> Self {
+            Error {
+                kind,
+                message: message.into(),
+            }
+        }
+
+        /// Gets the kind of this error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error::new(kind, String::new())
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.message.is_empty() {
+                write!(f, "{:?}", self.kind)
+            } else {
+                f.write_str(&self.message)
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Enumeration of possible methods to seek within a stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SeekFrom {
+        Start(u64),
+        End(i64),
+        Current(i64),
+    }
+
+    /// A source of bytes.
+    pub trait Read {
+        /// Reads some bytes into `buf`, returning how many were read. Zero means the end of
+        /// the stream, unless `buf` is empty.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Reads exactly enough bytes to fill `buf`.
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::UnexpectedEof,
+                            "failed to fill buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &mut buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+
+        /// Reads every remaining byte onto the end of `buf`, returning how many were read.
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let start = buf.len();
+            let mut chunk = [0u8; 512];
+            loop {
+                match self.read(&mut chunk) {
+                    Ok(0) => return Ok(buf.len() - start),
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    /// A sink for bytes.
+    pub trait Write {
+        /// Writes some of `buf`, returning how many bytes were accepted.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        /// Flushes any buffered bytes to their destination.
+        fn flush(&mut self) -> Result<()>;
+
+        /// Writes all of `buf`.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes from a sequence of buffers. By default only the first non-empty one is used.
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+            let buf = bufs
+                .iter()
+                .find(|b| !b.is_empty())
+                .map_or(&[][..], |b| &**b);
+            self.write(buf)
+        }
+    }
+
+    /// A stream with a position that can be moved.
+    pub trait Seek {
+        /// Moves to `pos`, returning the new position from the start of the stream.
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl<S: Seek + ?Sized> Seek for &mut S {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            (**self).seek(pos)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let count = buf.len().min(self.len());
+            let (head, tail) = self.split_at(count);
+            buf[..count].copy_from_slice(head);
+            *self = tail;
+            Ok(count)
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A byte slice for vectored writes.
+    #[derive(Debug, Clone, Copy)]
+    pub struct IoSlice<'a>(&'a [u8]);
+
+    impl<'a> IoSlice<'a> {
+        /// Wraps a byte slice.
+        pub fn new(buf: &'a [u8]) -> Self {
+            IoSlice(buf)
+        }
+
+        /// Moves past `n` bytes, dropping any slices that are used up.
+        pub fn advance_slices(bufs: &mut &mut [IoSlice<'a>], n: usize) {
+            let mut remove = 0;
+            let mut accumulated = 0;
+            for buf in bufs.iter() {
+                if accumulated + buf.len() > n {
+                    break;
+                }
+                accumulated += buf.len();
+                remove += 1;
+            }
+            *bufs = &mut core::mem::take(bufs)[remove..];
+            if let Some(first) = bufs.first_mut() {
+                first.0 = &first.0[n - accumulated..];
+            } else {
+                assert_eq!(n, accumulated, "advancing io slices beyond their length");
+            }
+        }
+    }
+
+    impl Deref for IoSlice<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            self.0
+        }
+    }
+
+    /// Wraps an in-memory buffer with a position so it can be read, written and seeked.
+    #[derive(Debug, Clone, Default)]
+    pub struct Cursor<T> {
+        inner: T,
+        position: u64,
+    }
+
+    impl<T> Cursor<T> {
+        /// Creates a new cursor at the start of `inner`.
+        pub fn new(inner: T) -> Self {
+            Cursor { inner, position: 0 }
+        }
+
+        /// Consumes the cursor, returning the underlying buffer.
+        pub fn into_inner(self) -> T {
+            self.inner
+        }
+
+        /// Gets a reference to the underlying buffer.
+        pub fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        /// Gets a mutable reference to the underlying buffer.
+        pub fn get_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
+
+        /// Gets the current position.
+        pub fn position(&self) -> u64 {
+            self.position
+        }
+
+        /// Sets the current position, which may be past the end of the buffer.
+        pub fn set_position(&mut self, position: u64) {
+            self.position = position;
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Cursor<T> {
+        fn remaining_slice(&self) -> &[u8] {
+            let data = self.inner.as_ref();
+            let start = (self.position as usize).min(data.len());
+            &data[start..]
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Read for Cursor<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let count = self.remaining_slice().read(buf)?;
+            self.position += count as u64;
+            Ok(count)
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let (base, offset) = match pos {
+                SeekFrom::Start(offset) => {
+                    self.position = offset;
+                    return Ok(offset);
+                }
+                SeekFrom::End(offset) => (self.inner.as_ref().len() as u64, offset),
+                SeekFrom::Current(offset) => (self.position, offset),
+            };
+            match base.checked_add_signed(offset) {
+                Some(position) => {
+                    self.position = position;
+                    Ok(position)
+                }
+                None => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "invalid seek to a negative or overflowing position",
+                )),
+            }
+        }
+    }
+
+    impl Write for Cursor<Vec<u8>> {
+        /// Overwrites bytes at the current position, extending the buffer as needed and
+        /// filling any gap left by seeking past the end with zeros.
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let start = usize::try_from(self.position).map_err(|_e| {
+                Error::new(ErrorKind::InvalidInput, "cursor position exceeds usize")
+            })?;
+            let end = start + buf.len();
+            let data = &mut self.inner;
+            if data.len() < start {
+                data.resize(start, 0);
+            }
+            let overlap = (data.len() - start).min(buf.len());
+            data[start..start + overlap].copy_from_slice(&buf[..overlap]);
+            data.extend_from_slice(&buf[overlap..]);
+            self.position = end as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -1,6 +1,24 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("buffer_io needs either the `std` or the `alloc` feature");
+
+extern crate alloc;
+
+pub mod io;
+
 #[macro_use]
 pub mod buffer {
-    use std::io::{Read, Seek, SeekFrom, Write};
+    use crate::io::{Cursor, IoSlice, Read, Seek, SeekFrom, Write};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use alloc::{format, vec};
+    use core::ops::Range;
+
+    #[cfg(feature = "derive")]
+    pub use buffer_io_derive::{BufferDeserialize, BufferSerialize};
+
     /// Specifies the position in a stream to use for seeking.
     #[derive(PartialEq)]
     pub enum SeekOrigin {
@@ -13,7 +31,7 @@ pub mod buffer {
     }
 
     /// Endianness refers to the order of bytes (or sometimes bits) within a binary representation of a number.
-    #[derive(PartialEq)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
     pub enum Endianness {
         /// The least significant byte (LSB) value, 0Dh, is at the lowest address.
         /// The other bytes follow in increasing order of significance.
@@ -25,368 +43,6777 @@ pub mod buffer {
         Big,
     }
 
-    /// Writes primitive types in binary to a stream and supports writing strings in a specific encoding.
-    pub struct BufferWriter<W: Write> {
-        pub writer: W,
+    /// The type of a single field within a fixed-layout record.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum RecordType {
+        U8,
+        U16,
+        U32,
+        U64,
+        I8,
+        I16,
+        I32,
+        I64,
+        F32,
+        F64,
+        /// A raw run of bytes of the given length.
+        Bytes(usize),
     }
 
-    impl<W: Write> BufferWriter<W>
-    where
-        W: Seek + Read + Write,
-    {
-        /// Creates a new BufferWriter instance
-        pub fn new(writer: W) -> Self {
-            BufferWriter { writer: writer }
-        }
-        /// Gets the position within the current stream.
-        pub fn position(&mut self) -> Result<u64, BufferError> {
-            self.seek(0, SeekOrigin::Current)
+    /// Describes a single field within a fixed-layout record.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct RecordField {
+        pub kind: RecordType,
+        /// The byte order of the field, little-endian if unset.
+        pub endianness: Option<Endianness>,
+    }
+
+    impl RecordField {
+        /// Creates a new little-endian RecordField
+        pub const fn new(kind: RecordType) -> Self {
+            RecordField {
+                kind,
+                endianness: None,
+            }
         }
-        /// Gets the length in bytes of the stream.
-        pub fn len(&mut self) -> Result<u64, BufferError> {
-            let old_pos = self.position()?;
-            let len = self.seek(0, SeekOrigin::End)?;
-            if old_pos != len {
-                self.seek(old_pos as i64, SeekOrigin::Begin)?;
+        /// Creates a new RecordField with the given byte order
+        pub const fn with_endianness(kind: RecordType, endianness: Endianness) -> Self {
+            RecordField {
+                kind,
+                endianness: Some(endianness),
             }
-            Ok(len)
         }
-        pub fn to_vec(&mut self) -> Result<Vec<u8>, BufferError> {
-            let mut out: Vec<u8> = vec![];
-            self.seek(0, SeekOrigin::Begin)?;
-            self.writer.read_to_end(&mut out).unwrap();
-            Ok(out)
+    }
+
+    /// A value decoded from a fixed-layout record.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum RecordValue {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        I8(i8),
+        I16(i16),
+        I32(i32),
+        I64(i64),
+        F32(f32),
+        F64(f64),
+        Bytes(Vec<u8>),
+    }
+
+    /// The axis of a geographic coordinate, which determines its valid range.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum CoordinateAxis {
+        /// Degrees north or south, from -90 to 90.
+        Latitude,
+        /// Degrees east or west, from -180 to 180.
+        Longitude,
+    }
+
+    impl CoordinateAxis {
+        fn contains(self, degrees: f64) -> bool {
+            let limit = match self {
+                CoordinateAxis::Latitude => 90.0,
+                CoordinateAxis::Longitude => 180.0,
+            };
+            (-limit..=limit).contains(&degrees)
         }
-        pub fn seek(&mut self, position: i64, origin: SeekOrigin) -> Result<u64, BufferError> {
-            match origin {
-                SeekOrigin::Begin => self.writer.seek(SeekFrom::Start(position as u64)),
-                SeekOrigin::Current => self.writer.seek(SeekFrom::Current(position)),
-                SeekOrigin::End => self.writer.seek(SeekFrom::End(position)),
+    }
+
+    /// The width of an integer field in a stream.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum IntWidth {
+        U8,
+        U16,
+        U32,
+    }
+
+    impl IntWidth {
+        /// Gets the number of bits in the width.
+        pub fn bits(self) -> u32 {
+            match self {
+                IntWidth::U8 => 8,
+                IntWidth::U16 => 16,
+                IntWidth::U32 => 32,
             }
-            .map(|o| o)
-            .map_err(|_e| BufferError::IndexOutOfRange { index: position })
         }
+    }
 
-        /// Writes a four-byte unsigned integer to the current stream
-        /// and advances the stream position by four bytes.
-        pub fn write_u32(&mut self, value: u32) -> Result<u64, BufferError> {
-            let data = &[
-                (value >> 0) as u8,
-                (value >> 8) as u8,
-                (value >> 16) as u8,
-                (value >> 24) as u8,
-            ];
-            self.writer
-                .write(data)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// Returns whether the bit at index `bit` is set in `flags`.
+    pub fn flag_is_set(flags: u32, bit: u32) -> bool {
+        bit < 32 && flags & (1 << bit) != 0
+    }
+
+    /// Returns `flags` with the bit at index `bit` set or cleared.
+    pub fn set_flag(flags: u32, bit: u32, on: bool) -> u32 {
+        let mask = 1u32.checked_shl(bit).unwrap_or(0);
+        if on {
+            flags | mask
+        } else {
+            flags & !mask
         }
+    }
 
-        /// Writes an eight-byte unsigned integer to the current stream
-        /// and advances the stream position by eight bytes.
-        pub fn write_u64(&mut self, value: u64) -> Result<u64, BufferError> {
-            let data = &[
-                (value >> 0) as u8,
-                (value >> 8) as u8,
-                (value >> 16) as u8,
-                (value >> 24) as u8,
-                (value >> 32) as u8,
-                (value >> 40) as u8,
-                (value >> 48) as u8,
-                (value >> 56) as u8,
-            ];
-            self.writer
-                .write(data)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// The order in which matrix elements are laid out in a stream.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum MatrixOrder {
+        /// Each row is stored contiguously.
+        RowMajor,
+        /// Each column is stored contiguously.
+        ColumnMajor,
+    }
+
+    /// The channel order of a color packed into a u32, from the most significant byte down.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ChannelOrder {
+        /// Red in the high byte, alpha in the low byte.
+        Rgba,
+        /// Blue in the high byte, alpha in the low byte.
+        Bgra,
+        /// Alpha in the high byte, blue in the low byte.
+        Argb,
+    }
+
+    impl ChannelOrder {
+        /// The bit shift of each of the red, green, blue and alpha channels.
+        fn shifts(self) -> [u32; 4] {
+            match self {
+                ChannelOrder::Rgba => [24, 16, 8, 0],
+                ChannelOrder::Bgra => [8, 16, 24, 0],
+                ChannelOrder::Argb => [16, 8, 0, 24],
+            }
         }
+    }
 
-        /// Writes a four-byte signed integer to the current stream
-        /// and advances the stream position by four bytes.
-        pub fn write_i32(&mut self, value: i32) -> Result<u64, BufferError> {
-            let data = &[
-                (value >> 0) as u8,
-                (value >> 8) as u8,
-                (value >> 16) as u8,
-                (value >> 24) as u8,
-            ];
-            self.writer
-                .write(data)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// The algorithm used to compute a single-byte checksum.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Checksum8 {
+        /// The wrapping sum of all bytes.
+        Sum,
+        /// The exclusive or of all bytes.
+        Xor,
+    }
+
+    impl Checksum8 {
+        /// Computes the checksum of the bytes.
+        pub fn compute(self, data: &[u8]) -> u8 {
+            match self {
+                Checksum8::Sum => data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)),
+                Checksum8::Xor => data.iter().fold(0u8, |acc, &b| acc ^ b),
+            }
         }
+    }
 
-        /// Writes a two-byte unsigned integer to the current stream
-        /// and advances the stream position by two bytes.
-        pub fn write_u16(&mut self, value: u16) -> Result<u64, BufferError> {
-            let data = &[(value >> 0) as u8, (value >> 8) as u8];
-            self.writer
-                .write(data)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// Specifies how the length of a string is recorded in a stream.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum StringPrefix {
+        /// A 7-bit encoded byte count precedes the string.
+        SevenBit,
+        /// A one-byte count precedes the string.
+        U8,
+        /// A two-byte count precedes the string.
+        U16,
+        /// A four-byte count precedes the string.
+        U32,
+        /// The string has no prefix and occupies exactly this many bytes, padded with NUL.
+        Fixed(usize),
+        /// The string has no prefix and is followed by a NUL terminator.
+        NullTerminated,
+    }
+
+    /// Specifies the character encoding of a string in a stream.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum StringEncoding {
+        /// UTF-8, one to four bytes per character.
+        Utf8,
+        /// UTF-16 with little-endian code units.
+        Utf16Le,
+        /// 7-bit ASCII, one byte per character.
+        Ascii,
+    }
+
+    /// Describes how a string is laid out in a stream.
+    /// All lengths and widths are measured in encoded bytes.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct StringOptions {
+        pub prefix: StringPrefix,
+        pub encoding: StringEncoding,
+        /// The maximum number of encoded bytes allowed, if any.
+        pub max_length: Option<usize>,
+    }
+
+    impl Default for StringOptions {
+        /// A 7-bit length prefixed UTF-8 string of any length.
+        fn default() -> Self {
+            StringOptions {
+                prefix: StringPrefix::SevenBit,
+                encoding: StringEncoding::Utf8,
+                max_length: None,
+            }
         }
+    }
 
-        /// Writes an unsigned byte to the current stream
-        /// and advances the stream position by one byte.
-        pub fn write_u8(&mut self, value: u8) -> Result<u64, BufferError> {
-            self.writer
-                .write(&[value])
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    fn encode_string(value: &str, encoding: StringEncoding) -> Result<Vec<u8>, BufferError> {
+        match encoding {
+            StringEncoding::Utf8 => Ok(value.as_bytes().to_vec()),
+            StringEncoding::Utf16Le => {
+                Ok(value.encode_utf16().flat_map(u16::to_le_bytes).collect())
+            }
+            StringEncoding::Ascii => match value.chars().find(|c| !c.is_ascii()) {
+                Some(ch) => Err(BufferError::UnrepresentableChar { ch }),
+                None => Ok(value.as_bytes().to_vec()),
+            },
         }
+    }
 
-        /// Write out an int 7 bits at a time. The high bit of the byte,
-        /// when on, tells reader to continue reading more bytes.
-        pub fn write_7bit_int(&mut self, value: i32) -> Result<(), BufferError> {
-            let mut v = value as u32;
-            while v >= 0x80 {
-                self.write_u8((v | 0x80) as u8)?;
-                v >>= 7;
+    fn decode_string(bytes: Vec<u8>, encoding: StringEncoding) -> Result<String, BufferError> {
+        match encoding {
+            StringEncoding::Utf8 => String::from_utf8(bytes).map_err(|_e| BufferError::IOFailure),
+            StringEncoding::Utf16Le => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(BufferError::IOFailure);
+                }
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16(&units).map_err(|_e| BufferError::IOFailure)
+            }
+            StringEncoding::Ascii => {
+                if !bytes.is_ascii() {
+                    return Err(BufferError::IOFailure);
+                }
+                String::from_utf8(bytes).map_err(|_e| BufferError::IOFailure)
             }
-            self.write_u8(v as u8)?;
-            Ok(())
         }
+    }
 
-        /// Writes a length-prefixed string to this stream in UTF8-encoding
-        /// and advances the current position of the stream in accordance with the encoding
-        /// used and the specific characters being written to the stream.
-        pub fn write_string(&mut self, value: String) -> Result<u64, BufferError> {
-            let bytes = value.as_bytes();
-            self.write_7bit_int(bytes.len() as i32)?;
-            self.writer
-                .write(bytes)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// Returns the size in bytes of a single code unit for the encoding.
+    fn code_unit_size(encoding: StringEncoding) -> usize {
+        match encoding {
+            StringEncoding::Utf16Le => 2,
+            _ => 1,
         }
+    }
 
-        /// Writes a section of a bytes to the current stream, and advances the current position of the stream
-        pub fn write_bytes(&mut self, value: &Vec<u8>) -> Result<u64, BufferError> {
-            self.writer
-                .write(value)
-                .map(|o| o as u64)
-                .map_err(|_e| BufferError::IOFailure)
+    /// Ensures a string length does not exceed the configured maximum.
+    fn check_string_length(length: u64, max: Option<usize>) -> Result<(), BufferError> {
+        match max {
+            Some(max) if length > max as u64 => Err(BufferError::StringTooLong {
+                length: length as usize,
+                max,
+            }),
+            _ => Ok(()),
         }
     }
 
-    /// Reads primitive data types as binary values in a specific encoding.
-    pub struct BufferReader<R: Read> {
-        pub reader: R,
+    /// Describes a single named field within a packed bitfield layout.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct BitField {
+        /// The name of the field, used when reporting errors.
+        pub name: &'static str,
+        /// The width of the field in bits, from 1 to 64.
+        pub bits: u32,
     }
 
-    impl<R: Read> BufferReader<R>
-    where
-        R: Seek + Read + Write,
-    {
-        /// Creates a new BufferReader
-        pub fn new(reader: R) -> Self {
-            BufferReader { reader: reader }
+    impl BitField {
+        /// Creates a new BitField
+        pub const fn new(name: &'static str, bits: u32) -> Self {
+            BitField { name, bits }
         }
-        /// Gets the position within the current stream.
-        pub fn position(&mut self) -> Result<u64, BufferError> {
-            self.seek(0, SeekOrigin::Current)
+    }
+
+    /// Validates a bitfield layout and returns the number of bytes it occupies.
+    fn bitfield_size(layout: &[BitField]) -> Result<u64, BufferError> {
+        let mut total: u64 = 0;
+        for field in layout {
+            if field.bits == 0 || field.bits > 64 {
+                return Err(BufferError::InvalidBitWidth {
+                    name: field.name.to_string(),
+                    bits: field.bits,
+                });
+            }
+            total += field.bits as u64;
         }
-        /// Gets the length in bytes of the stream.
-        pub fn len(&mut self) -> Result<u64, BufferError> {
-            let old_pos = self.position()?;
-            let len = self.seek(0, SeekOrigin::End)?;
-            if old_pos != len {
-                self.seek(old_pos as i64, SeekOrigin::Begin)?;
+        Ok(total.div_ceil(8))
+    }
+
+    /// The number of bytes moved at a time when streaming a string to a sink or reading a
+    /// length-prefixed value. Reads allocate at most this much ahead of the data actually
+    /// arriving, so a corrupt length prefix can't force a huge allocation.
+    pub(crate) const STREAM_CHUNK_SIZE: usize = 8 * 1024;
+
+    /// The bits given to each of the three smallest components of a compressed quaternion.
+    const QUATERNION_COMPONENT_BITS: u32 = 10;
+
+    /// The largest magnitude the three smallest components of a unit quaternion can have.
+    const QUATERNION_COMPONENT_MAX: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+    /// The longest run or literal group a single run-length token can describe.
+    const RLE_MAX_GROUP: usize = 1 << 29;
+
+    /// Returns the bits of `mask` that are not covered by any of the bit indices.
+    fn uncovered_bits<F>(mask: u32, fields: &[(u32, F)]) -> u32 {
+        fields.iter().fold(mask, |rest, (bit, _)| {
+            rest & !1u32.checked_shl(*bit).unwrap_or(0)
+        })
+    }
+
+    /// Reads until `buffer` is full or the stream ends, returning how many bytes were read.
+    /// Unlike `read_exact`, a short read reports how much was available.
+    fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize, BufferError> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            if read_step(reader.read(&mut buffer[filled..]), &mut filled)? {
+                break;
             }
-            Ok(len)
         }
-        pub fn seek(&mut self, position: i64, origin: SeekOrigin) -> Result<u64, BufferError> {
-            match origin {
-                SeekOrigin::Begin => self.reader.seek(SeekFrom::Start(position as u64)),
-                SeekOrigin::Current => self.reader.seek(SeekFrom::Current(position)),
-                SeekOrigin::End => self.reader.seek(SeekFrom::End(position)),
+        Ok(filled)
+    }
+
+    /// Adds the result of a single `read` call to `filled`, returning whether the stream has
+    /// ended. Interrupted reads count as nothing read, so the caller tries again. The sync and
+    /// async readers both fill buffers through this so they handle short reads the same way.
+    pub(crate) fn read_step(
+        result: crate::io::Result<usize>,
+        filled: &mut usize,
+    ) -> Result<bool, BufferError> {
+        match result {
+            Ok(0) => Ok(true),
+            Ok(n) => {
+                *filled += n;
+                Ok(false)
             }
-            .map(|o| o as u64)
-            .map_err(|_e| BufferError::IndexOutOfRange { index: position })
+            Err(e) if e.kind() == crate::io::ErrorKind::Interrupted => Ok(false),
+            Err(e) => Err(BufferError::ReadFailure(e)),
         }
+    }
 
-        /// Reads in a 32-bit integer in compressed format.
-        pub fn read_7bit_int(&mut self) -> Result<i32, BufferError> {
-            let mut count: i32 = 0;
-            let mut shift = 0;
-            let mut b: u8 = 0;
-            while {
-                // Check for a corrupted stream.  Read a max of 5 bytes.
-                // In a future version, add a DataFormatException.
-                if shift == 5 * 7 {
-                    // 5 bytes max per Int32, shift += 7
-                    // too many bytes in what should have been a 7 bit encoded i32.
-                    return Err(BufferError::IOFailure);
-                }
-                // read_u8 handles end of stream cases for us.
-                b = self.read_u8()?;
-                count |= ((b & 0x7F) as i32) << shift;
-                shift += 7;
-                (b & 0x80) != 0
-            } {}
-            Ok(count)
+    /// Rejects the byte of a 7-bit encoded i32 that starts at bit `shift` if it holds bits
+    /// past the 31st, which would otherwise be shifted out or make the value negative.
+    pub(crate) fn check_7bit_int_byte(shift: u32, b: u8) -> Result<(), BufferError> {
+        // The fifth byte only has room for bits 28 to 30.
+        if shift == 4 * 7 && b & 0x7F > 0x07 {
+            return Err(BufferError::InvalidData {
+                message: format!("7-bit encoded i32 overflows 31 bits (last byte {:#04x})", b),
+            });
         }
-        /// Reads a null-terminated string from the buffer
-        pub fn read_string(&mut self) -> Result<String, BufferError> {
-            let string_length = self.read_7bit_int()?;
-            if string_length < 0 {
-                return Err(BufferError::IOFailure);
+        Ok(())
+    }
+
+    /// Fails with `EndOfStream` if fewer than `needed` bytes were filled.
+    pub(crate) fn check_filled(needed: usize, filled: usize) -> Result<(), BufferError> {
+        if filled < needed {
+            return Err(BufferError::EndOfStream {
+                needed: needed as u64,
+                available: filled as u64,
+            });
+        }
+        Ok(())
+    }
+
+    /// Widens an `EndOfStream` from filling one chunk of a `count`-byte read that started at
+    /// `start` so it reports the whole count, not just the chunk that ran out.
+    pub(crate) fn chunk_error(error: BufferError, count: u64, start: usize) -> BufferError {
+        match error {
+            BufferError::EndOfStream { available, .. } => BufferError::EndOfStream {
+                needed: count,
+                available: start as u64 + available,
+            },
+            e => e,
+        }
+    }
+
+    /// Rounds a timestamp to the nearest multiple of the quantum.
+    fn quantize(t_ns: i64, quantum_ns: i64) -> Result<i64, BufferError> {
+        if quantum_ns <= 0 {
+            return Err(BufferError::InvalidQuantum { quantum_ns });
+        }
+        let quantum = quantum_ns as i128;
+        let rounded = (t_ns as i128 + quantum / 2).div_euclid(quantum) * quantum;
+        Ok(rounded.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+
+    /// Stands in for the float methods that `core` doesn't provide without `std`. Test builds
+    /// link `std` for the harness, which brings the inherent methods back.
+    #[cfg(not(any(feature = "std", test)))]
+    trait FloatMath {
+        fn round(self) -> Self;
+        fn sqrt(self) -> Self;
+    }
+
+    #[cfg(not(any(feature = "std", test)))]
+    impl FloatMath for f64 {
+        /// Rounds half away from zero, as `f64::round` does.
+        fn round(self) -> f64 {
+            // Anything this large is already a whole number, and NaN passes through.
+            if self.is_nan() || self.abs() >= 4_503_599_627_370_496.0 {
+                return self;
             }
-            if string_length == 0 {
-                return Ok(String::default());
+            let truncated = self as i64 as f64;
+            let fraction = self - truncated;
+            if fraction >= 0.5 {
+                truncated + 1.0
+            } else if fraction <= -0.5 {
+                truncated - 1.0
+            } else {
+                truncated
             }
-            let chars = self.read_bytes(string_length as u64)?;
-            String::from_utf8(chars)
-                .map(|o| o)
-                .map_err(|_e| BufferError::IOFailure)
         }
 
-        /// Reads a 4-byte unsigned integer from the current vector
-        /// and advances the position of the cursor by four bytes.
-        pub fn read_u32(&mut self) -> Result<u32, BufferError> {
-            let size = std::mem::size_of::<u32>() as u64;
-            if self.position()? + size > self.len()? {
-                return Err(BufferError::EndOfStream);
+        fn sqrt(self) -> f64 {
+            if self.is_nan() || self < 0.0 {
+                return f64::NAN;
             }
-            let mut buffer = [0u8; 4];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| {
-                    ((buffer[0] as u32) << 0)
-                        | ((buffer[1] as u32) << 8)
-                        | ((buffer[2] as u32) << 16)
-                        | ((buffer[3] as u32) << 24)
-                })
+            if self == 0.0 || self == f64::INFINITY {
+                return self;
+            }
+            // Halving the exponent gives a first guess within a few percent, and each Newton
+            // step roughly doubles the number of correct digits.
+            let mut root = f64::from_bits((self.to_bits() >> 1) + (1023 << 51));
+            for _ in 0..6 {
+                root = 0.5 * (root + self / root);
+            }
+            root
         }
+    }
 
-        /// Reads a 8-byte unsigned integer from the current vector
-        /// and advances the position of the cursor by eight bytes.
-        pub fn read_u64(&mut self) -> Result<u64, BufferError> {
-            let size = std::mem::size_of::<u64>() as u64;
-            if self.position()? + size > self.len()? {
-                return Err(BufferError::EndOfStream);
-            }
-            let mut buffer = vec![0u8; 8];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| {
-                    let lo = (buffer[0] as u32)
-                        | (buffer[1] as u32) << 8
-                        | (buffer[2] as u32) << 16
-                        | (buffer[3] as u32) << 24;
-                    let hi = (buffer[4] as u32)
-                        | (buffer[5] as u32) << 8
-                        | (buffer[6] as u32) << 16
-                        | (buffer[7] as u32) << 24;
-
-                    (hi as u64) << 32 | lo as u64
-                })
+    #[cfg(not(any(feature = "std", test)))]
+    impl FloatMath for f32 {
+        fn round(self) -> f32 {
+            (self as f64).round() as f32
         }
 
-        /// Reads a 4-byte signed integer from the current vector
-        /// and advances the current position of the cursor by four bytes.
-        pub fn read_i32(&mut self) -> Result<i32, BufferError> {
-            let size = std::mem::size_of::<i32>() as u64;
-            if self.position()? + size > self.len()? {
-                return Err(BufferError::EndOfStream);
-            }
-            let mut buffer = [0u8; 4];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| {
-                    ((buffer[0] as i32) << 0)
-                        | ((buffer[1] as i32) << 8)
-                        | ((buffer[2] as i32) << 16)
-                        | ((buffer[3] as i32) << 24)
-                })
+        fn sqrt(self) -> f32 {
+            (self as f64).sqrt() as f32
         }
+    }
 
-        /// Reads a 2-byte unsigned integer from the current vector using little-endian encoding
-        /// and advances the position of the cursor by two bytes.
-        pub fn read_u16(&mut self) -> Result<u16, BufferError> {
-            let size = std::mem::size_of::<u16>() as u64;
-            if self.position()? + size > self.len()? {
-                return Err(BufferError::EndOfStream);
-            }
-            let mut buffer = [0u8; 2];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| (buffer[0] as u16) | (buffer[1] as u16))
+    /// Returns the number of bytes needed to bring `position` to a multiple of `alignment`,
+    /// which must be a power of two.
+    fn padding_for(position: u64, alignment: u64) -> Result<u64, BufferError> {
+        if !alignment.is_power_of_two() {
+            return Err(BufferError::InvalidData {
+                message: format!("alignment {} is not a power of two", alignment),
+            });
         }
+        Ok(position.wrapping_neg() & (alignment - 1))
+    }
 
-        /// Reads the next byte from the current vector
-        /// and advances the current position of the cursor by one byte.
-        pub fn read_u8(&mut self) -> Result<u8, BufferError> {
-            let size = std::mem::size_of::<u8>() as u64;
-            if self.position()? + size > self.len()? {
-                return Err(BufferError::EndOfStream);
+    /// Formats bytes like `hexdump -C`: an offset, sixteen bytes in hex and their printable ASCII.
+    fn hex_dump(data: &[u8]) -> String {
+        let mut dump = String::new();
+        for (line, chunk) in data.chunks(16).enumerate() {
+            dump.push_str(&format!("{:08x}  ", line * 16));
+            for i in 0..16 {
+                match chunk.get(i) {
+                    Some(b) => dump.push_str(&format!("{:02x} ", b)),
+                    None => dump.push_str("   "),
+                }
+                if i == 7 {
+                    dump.push(' ');
+                }
             }
-            let mut buffer = [0u8; 1];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| buffer[0])
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            dump.push_str(&format!(" |{}|\n", ascii));
         }
+        dump
+    }
 
-        /// Reads the specified number of bytes from the current stream
-        /// into a byte array and advances the current position by that number of bytes.
-        pub fn read_bytes(&mut self, count: u64) -> Result<Vec<u8>, BufferError> {
-            if self.position()? + count > self.len()? {
-                return Err(BufferError::EndOfStream);
+    /// Computes the Adler-32 checksum of the bytes, as used by zlib.
+    pub fn adler32(data: &[u8]) -> u32 {
+        const MOD_ADLER: u32 = 65521;
+        let (mut a, mut b) = (1u32, 0u32);
+        // 5552 is the most bytes that can be summed before `b` could overflow a u32.
+        for chunk in data.chunks(5552) {
+            for &byte in chunk {
+                a += byte as u32;
+                b += a;
             }
-            let mut buffer = vec![0u8; count as usize];
-            self.reader
-                .read_exact(&mut buffer)
-                .map_err(|e| BufferError::ReadFailure(e))
-                .map(|_b| buffer)
+            a %= MOD_ADLER;
+            b %= MOD_ADLER;
         }
+        (b << 16) | a
+    }
 
-        /// Reads the specified number of bytes at a pointer from the current stream
-        /// into a byte array without advancing the current position.
-        pub fn read_bytes_at(&mut self, offset: u64, count: u64) -> Result<Vec<u8>, BufferError> {
-            if offset + count > self.len()? {
-                return Err(BufferError::EndOfStream);
+    /// Computes the CRC-32 checksum of the bytes, using the IEEE polynomial as zlib and PNG do.
+    pub fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
             }
-            let current_pos = self.position()?;
-            self.seek(offset as i64, SeekOrigin::Begin)?;
-            let buffer = self.read_bytes(count)?;
-            self.seek(current_pos as i64, SeekOrigin::Begin)?;
-            Ok(buffer)
         }
+        !crc
     }
 
-    #[derive(Debug, thiserror::Error)]
-    pub enum BufferError {
-        #[error("seek index ({index}) was out of range. Must be non-negative and less than the size of the collection.")]
-        IndexOutOfRange { index: i64 },
-        #[error("attempted to read past the end of a stream.")]
-        EndOfStream,
-        #[error("unable to read bytes from buffer: {0:?}")]
-        ReadFailure(#[from] std::io::Error),
-        #[error("unable to write data to buffer.")]
-        IOFailure,
+    /// Collects byte slices to be emitted together by `BufferWriter::write_batch`.
+    #[derive(Default)]
+    pub struct WriteBatch<'a> {
+        slices: Vec<IoSlice<'a>>,
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        use crate::buffer::{BufferReader, BufferWriter, SeekOrigin};
-        use std::io::Cursor;
-        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
-        buffer.write_u32(9001).unwrap();
-        buffer.write_u32(9002).unwrap();
-        buffer.write_string("Hello World!".to_string()).unwrap();
-        buffer.seek(0, SeekOrigin::Begin).unwrap();
-        buffer.write_u32(9003).unwrap();
-        let data = buffer.to_vec().unwrap();
-        let mut reader = BufferReader::new(Cursor::new(data));
-        assert_eq!(9003, reader.read_u32().unwrap());
-        assert_eq!(9002, reader.read_u32().unwrap());
-        assert_eq!("Hello World!", reader.read_string().unwrap());
+    impl<'a> WriteBatch<'a> {
+        /// Creates a new, empty WriteBatch
+        pub fn new() -> Self {
+            WriteBatch::default()
+        }
+        /// Queues a slice to be written after those already in the batch.
+        pub fn push(&mut self, data: &'a [u8]) {
+            self.slices.push(IoSlice::new(data));
+        }
+        /// Gets the total number of bytes queued.
+        pub fn len(&self) -> usize {
+            self.slices.iter().map(|s| s.len()).sum()
+        }
+        /// Returns true when no bytes are queued.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    /// Writes primitive types in binary to a stream and supports writing strings in a specific encoding.
+    ///
+    /// Every write is retried until all of its bytes are accepted, so a stream that takes only part
+    /// of a value at a time can't truncate it. Write methods return the number of bytes written,
+    /// which is the full length of the value.
+    ///
+    /// Any `Write` sink can be written to. Methods that move around the stream, such as
+    /// `position`, `seek`, `mark_label` and `to_vec`, also need it to be `Seek + Read`.
+    pub struct BufferWriter<W: Write> {
+        writer: W,
+        endianness: Endianness,
+        labels: BTreeMap<String, u64>,
+        last_timestamp: i64,
+        bytes_written: u64,
+    }
+
+    impl<W: Write> BufferWriter<W> {
+        /// Creates a new BufferWriter instance that writes little-endian integers
+        pub fn new(writer: W) -> Self {
+            BufferWriter::with_endianness(writer, Endianness::Little)
+        }
+
+        /// Creates a new BufferWriter instance that writes integers in the given byte order
+        pub fn with_endianness(writer: W, endianness: Endianness) -> Self {
+            BufferWriter {
+                writer,
+                endianness,
+                labels: BTreeMap::new(),
+                last_timestamp: 0,
+                bytes_written: 0,
+            }
+        }
+
+        /// Gets a reference to the underlying writer.
+        pub fn get_ref(&self) -> &W {
+            &self.writer
+        }
+
+        /// Gets a mutable reference to the underlying writer.
+        /// Writing to it directly bypasses the labels and timestamps this writer tracks, and those
+        /// bytes aren't counted by `bytes_written`.
+        pub fn get_mut(&mut self) -> &mut W {
+            &mut self.writer
+        }
+
+        /// Consumes the BufferWriter, returning the underlying writer.
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+
+        /// Writes all of `data`, retrying short writes, and returns its length.
+        fn write_raw(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+            self.writer
+                .write_all(data)
+                .map_err(|_e| BufferError::IOFailure)?;
+            self.bytes_written += data.len() as u64;
+            Ok(data.len() as u64)
+        }
+
+        /// Gets the total number of bytes written through this writer, including any that
+        /// overwrote earlier bytes after a seek.
+        pub fn bytes_written(&self) -> u64 {
+            self.bytes_written
+        }
+
+        /// Flushes the underlying writer, so buffered sinks send what has been written so far.
+        /// This is never done implicitly, as a failure on drop could not be reported.
+        pub fn flush(&mut self) -> Result<(), BufferError> {
+            self.writer.flush().map_err(|_e| BufferError::IOFailure)
+        }
+
+        /// Writes a four-byte unsigned integer to the current stream
+        /// and advances the stream position by four bytes.
+        pub fn write_u32(&mut self, value: u32) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes an eight-byte unsigned integer to the current stream
+        /// and advances the stream position by eight bytes.
+        pub fn write_u64(&mut self, value: u64) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes a four-byte signed integer to the current stream
+        /// and advances the stream position by four bytes.
+        pub fn write_i32(&mut self, value: i32) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes a two-byte unsigned integer to the current stream
+        /// and advances the stream position by two bytes.
+        pub fn write_u16(&mut self, value: u16) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes an unsigned byte to the current stream
+        /// and advances the stream position by one byte.
+        pub fn write_u8(&mut self, value: u8) -> Result<u64, BufferError> {
+            self.write_raw(&[value])
+        }
+
+        /// Writes a signed byte to the current stream
+        /// and advances the stream position by one byte.
+        pub fn write_i8(&mut self, value: i8) -> Result<u64, BufferError> {
+            self.write_u8(value as u8)
+        }
+
+        /// Writes a two-byte signed integer to the current stream
+        /// and advances the stream position by two bytes.
+        pub fn write_i16(&mut self, value: i16) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes an eight-byte signed integer to the current stream
+        /// and advances the stream position by eight bytes.
+        pub fn write_i64(&mut self, value: i64) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes a sixteen-byte unsigned integer to the current stream
+        /// and advances the stream position by sixteen bytes.
+        pub fn write_u128(&mut self, value: u128) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes a sixteen-byte signed integer to the current stream
+        /// and advances the stream position by sixteen bytes.
+        pub fn write_i128(&mut self, value: i128) -> Result<u64, BufferError> {
+            let data = match self.endianness {
+                Endianness::Little => value.to_le_bytes(),
+                Endianness::Big => value.to_be_bytes(),
+            };
+            self.write_raw(&data)
+        }
+
+        /// Writes a four-byte IEEE-754 float to the current stream, preserving its exact bit pattern,
+        /// and advances the stream position by four bytes.
+        pub fn write_f32(&mut self, value: f32) -> Result<u64, BufferError> {
+            self.write_u32(value.to_bits())
+        }
+
+        /// Writes an eight-byte IEEE-754 float to the current stream, preserving its exact bit pattern,
+        /// and advances the stream position by eight bytes.
+        pub fn write_f64(&mut self, value: f64) -> Result<u64, BufferError> {
+            self.write_u64(value.to_bits())
+        }
+
+        /// Writes a character as its Unicode scalar value, a four-byte unsigned integer.
+        pub fn write_char(&mut self, value: char) -> Result<u64, BufferError> {
+            self.write_u32(value as u32)
+        }
+
+        /// Writes a boolean as a single byte, 1 for true and 0 for false.
+        pub fn write_bool(&mut self, value: bool) -> Result<u64, BufferError> {
+            self.write_u8(value as u8)
+        }
+
+        /// Write out an int 7 bits at a time. The high bit of the byte,
+        /// when on, tells reader to continue reading more bytes.
+        /// Negative values are written as five bytes, which `read_7bit_int` rejects.
+        pub fn write_7bit_int(&mut self, value: i32) -> Result<(), BufferError> {
+            let mut v = value as u32;
+            while v >= 0x80 {
+                self.write_u8((v | 0x80) as u8)?;
+                v >>= 7;
+            }
+            self.write_u8(v as u8)?;
+            Ok(())
+        }
+
+        /// Write out a 64-bit int 7 bits at a time, the same way as `write_7bit_int`.
+        /// Negative values always take the full ten bytes.
+        pub fn write_7bit_long(&mut self, value: i64) -> Result<(), BufferError> {
+            let mut v = value as u64;
+            while v >= 0x80 {
+                self.write_u8((v | 0x80) as u8)?;
+                v >>= 7;
+            }
+            self.write_u8(v as u8)?;
+            Ok(())
+        }
+
+        /// Writes a signed integer zigzag encoded then 7 bits at a time, matching Protobuf's sint64,
+        /// so values near zero stay short whatever their sign.
+        pub fn write_varint_zigzag(&mut self, value: i64) -> Result<(), BufferError> {
+            self.write_7bit_long((value << 1) ^ (value >> 63))
+        }
+
+        /// Writes a length-prefixed string to this stream in UTF8-encoding
+        /// and advances the current position of the stream in accordance with the encoding
+        /// used and the specific characters being written to the stream.
+        pub fn write_string(&mut self, value: String) -> Result<u64, BufferError> {
+            self.write_string_full(&value, StringOptions::default())
+        }
+
+        /// Writes a UTF-8 string with the given prefix, failing with `StringTooLong` if the
+        /// prefix cannot represent its length. A shorthand for `write_string_full`.
+        pub fn write_string_prefixed(
+            &mut self,
+            value: &str,
+            prefix: StringPrefix,
+        ) -> Result<u64, BufferError> {
+            self.write_string_full(
+                value,
+                StringOptions {
+                    prefix,
+                    ..StringOptions::default()
+                },
+            )
+        }
+
+        /// Writes a string laid out according to the options and returns the number of encoded
+        /// string bytes written, not counting any prefix, terminator or padding.
+        pub fn write_string_full(
+            &mut self,
+            value: &str,
+            opts: StringOptions,
+        ) -> Result<u64, BufferError> {
+            let bytes = encode_string(value, opts.encoding)?;
+            check_string_length(bytes.len() as u64, opts.max_length)?;
+            let prefix_max = match opts.prefix {
+                StringPrefix::SevenBit => i32::MAX as usize,
+                StringPrefix::U8 => u8::MAX as usize,
+                StringPrefix::U16 => u16::MAX as usize,
+                StringPrefix::U32 => u32::MAX as usize,
+                StringPrefix::Fixed(width) => width,
+                StringPrefix::NullTerminated => usize::MAX,
+            };
+            check_string_length(bytes.len() as u64, Some(prefix_max))?;
+            let unit = code_unit_size(opts.encoding);
+            match opts.prefix {
+                StringPrefix::SevenBit => self.write_7bit_int(bytes.len() as i32)?,
+                StringPrefix::U8 => {
+                    self.write_u8(bytes.len() as u8)?;
+                }
+                StringPrefix::U16 => {
+                    self.write_u16(bytes.len() as u16)?;
+                }
+                StringPrefix::U32 => {
+                    self.write_u32(bytes.len() as u32)?;
+                }
+                StringPrefix::Fixed(_) => {}
+                StringPrefix::NullTerminated => {
+                    if bytes.chunks(unit).any(|c| c.iter().all(|&b| b == 0)) {
+                        return Err(BufferError::UnrepresentableChar { ch: '\0' });
+                    }
+                }
+            }
+            let written = self.write_raw(&bytes)?;
+            match opts.prefix {
+                StringPrefix::Fixed(width) => {
+                    self.write_bytes(&vec![0u8; width - bytes.len()])?;
+                }
+                StringPrefix::NullTerminated => {
+                    self.write_bytes(&vec![0u8; unit])?;
+                }
+                _ => {}
+            }
+            Ok(written)
+        }
+
+        /// Writes the string as UTF-8 followed by a NUL terminator, returning the number of
+        /// string bytes written. Fails with `InvalidData` if the string itself contains a NUL.
+        pub fn write_cstring(&mut self, value: &str) -> Result<u64, BufferError> {
+            if let Some(index) = value.bytes().position(|b| b == 0) {
+                return Err(BufferError::InvalidData {
+                    message: format!("interior NUL at byte {}", index),
+                });
+            }
+            let written = self.write_raw(value.as_bytes())?;
+            self.write_u8(0)?;
+            Ok(written)
+        }
+
+        /// Writes the string as UTF-8 into a field of exactly `width` bytes, filling the rest with `pad`.
+        /// A string longer than the field fails with `StringTooLong` rather than being truncated,
+        /// since cutting it short could split a character or silently lose data.
+        pub fn write_fixed_string(
+            &mut self,
+            value: &str,
+            width: usize,
+            pad: u8,
+        ) -> Result<u64, BufferError> {
+            check_string_length(value.len() as u64, Some(width))?;
+            let written = self.write_raw(value.as_bytes())?;
+            self.write_bytes(&vec![pad; width - value.len()])?;
+            Ok(written)
+        }
+
+        /// Writes each value with `write` after a 7-bit encoded element count.
+        fn write_array<T: Copy>(
+            &mut self,
+            values: &[T],
+            write: fn(&mut Self, T) -> Result<u64, BufferError>,
+        ) -> Result<u64, BufferError> {
+            if values.len() > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: values.len() as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            self.write_7bit_int(values.len() as i32)?;
+            let mut written = 0;
+            for &value in values.iter() {
+                written += write(self, value)?;
+            }
+            Ok(written)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_u16_array(&mut self, values: &[u16]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_u16)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_u32_array(&mut self, values: &[u32]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_u32)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_u64_array(&mut self, values: &[u64]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_u64)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_i16_array(&mut self, values: &[i16]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_i16)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_i32_array(&mut self, values: &[i32]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_i32)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_i64_array(&mut self, values: &[i64]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_i64)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_f32_array(&mut self, values: &[f32]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_f32)
+        }
+
+        /// Writes the values prefixed by their 7-bit encoded count, each in the configured byte order.
+        /// Returns the number of element bytes written, not counting the prefix.
+        pub fn write_f64_array(&mut self, values: &[f64]) -> Result<u64, BufferError> {
+            self.write_array(values, Self::write_f64)
+        }
+
+        /// Writes a presence byte, 0 for `None` or 1 for `Some`, followed by the value written by
+        /// `f` when there is one.
+        pub fn write_option<T, F>(&mut self, value: Option<T>, f: F) -> Result<(), BufferError>
+        where
+            F: FnOnce(&mut Self, T) -> Result<u64, BufferError>,
+        {
+            self.write_bool(value.is_some())?;
+            if let Some(value) = value {
+                f(self, value)?;
+            }
+            Ok(())
+        }
+
+        /// Writes an optional length-prefixed string behind a presence byte.
+        pub fn write_optional_string(&mut self, value: Option<&str>) -> Result<(), BufferError> {
+            self.write_option(value, |writer, value| {
+                writer.write_string_full(value, StringOptions::default())
+            })
+        }
+
+        /// Writes the bytes prefixed by their 7-bit encoded length, so they can be read back
+        /// with `read_blob` without knowing the count in advance.
+        pub fn write_blob(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+            if data.len() > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: data.len() as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            self.write_7bit_int(data.len() as i32)?;
+            self.write_bytes(data)
+        }
+
+        /// Writes a string as UTF-16 prefixed by its 7-bit encoded length in code units, not bytes.
+        /// Each code unit is written as a u16 in the configured byte order.
+        pub fn write_string_utf16(&mut self, value: &str) -> Result<u64, BufferError> {
+            let units: Vec<u16> = value.encode_utf16().collect();
+            if units.len() > i32::MAX as usize {
+                return Err(BufferError::StringTooLong {
+                    length: units.len(),
+                    max: i32::MAX as usize,
+                });
+            }
+            self.write_7bit_int(units.len() as i32)?;
+            for &unit in units.iter() {
+                self.write_u16(unit)?;
+            }
+            Ok(units.len() as u64 * 2)
+        }
+
+        /// Writes a section of a bytes to the current stream, and advances the current position of the stream
+        pub fn write_bytes(&mut self, value: &[u8]) -> Result<u64, BufferError> {
+            self.write_raw(value)
+        }
+
+        /// Packs the values into the bitfield layout, most significant bit first, and writes them.
+        /// If the layout does not fill a whole number of bytes, the trailing bits of the last byte are zero.
+        pub fn write_bitfields(
+            &mut self,
+            layout: &[BitField],
+            values: &[u64],
+        ) -> Result<u64, BufferError> {
+            if layout.len() != values.len() {
+                return Err(BufferError::LayoutMismatch {
+                    expected: layout.len(),
+                    actual: values.len(),
+                });
+            }
+            let mut data = vec![0u8; bitfield_size(layout)? as usize];
+            let mut bit = 0usize;
+            for (field, &value) in layout.iter().zip(values) {
+                if field.bits < 64 && value >> field.bits != 0 {
+                    return Err(BufferError::BitFieldOverflow {
+                        name: field.name.to_string(),
+                        value,
+                    });
+                }
+                for i in (0..field.bits).rev() {
+                    if (value >> i) & 1 != 0 {
+                        data[bit / 8] |= 0x80 >> (bit % 8);
+                    }
+                    bit += 1;
+                }
+            }
+            self.write_raw(&data)
+        }
+
+        /// Writes a sparse array of `len` logical elements as its length, the number of entries,
+        /// then each entry's index and value. Indices must be in range and strictly ascending,
+        /// and `len` must fit in an `i32`; that also bounds the entry count and every index.
+        pub fn write_sparse<T, O, F>(
+            &mut self,
+            len: usize,
+            entries: &[(usize, T)],
+            mut write_fn: F,
+        ) -> Result<(), BufferError>
+        where
+            F: FnMut(&mut Self, &T) -> Result<O, BufferError>,
+        {
+            if len > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: len as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            let mut next = 0;
+            for (index, _) in entries {
+                if *index < next || *index >= len {
+                    return Err(BufferError::InvalidSparseIndex { index: *index });
+                }
+                next = index + 1;
+            }
+            self.write_7bit_int(len as i32)?;
+            self.write_7bit_int(entries.len() as i32)?;
+            for (index, value) in entries {
+                self.write_7bit_int(*index as i32)?;
+                write_fn(self, value)?;
+            }
+            Ok(())
+        }
+
+        /// Writes a presence byte followed by `current` only if it differs from `previous`.
+        /// Returns whether the value was written.
+        pub fn write_delta_u32(
+            &mut self,
+            previous: u32,
+            current: u32,
+        ) -> Result<bool, BufferError> {
+            let changed = previous != current;
+            self.write_u8(changed as u8)?;
+            if changed {
+                self.write_u32(current)?;
+            }
+            Ok(changed)
+        }
+
+        /// Writes an optional u32, using `sentinel` to represent `None`.
+        /// A present value equal to the sentinel cannot be represented and is rejected.
+        pub fn write_optional_sentinel_u32(
+            &mut self,
+            value: Option<u32>,
+            sentinel: u32,
+        ) -> Result<u64, BufferError> {
+            match value {
+                Some(value) if value == sentinel => Err(BufferError::SentinelCollision { value }),
+                Some(value) => self.write_u32(value),
+                None => self.write_u32(sentinel),
+            }
+        }
+
+        /// Writes the bytes prefixed by their length as a big-endian u32,
+        /// the record shape used by many TLV and TLS-style protocols.
+        pub fn write_vec_u32be(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+            if data.len() as u64 > u32::MAX as u64 {
+                return Err(BufferError::VecTooLong {
+                    length: data.len() as u64,
+                    max: u32::MAX as u64,
+                });
+            }
+            self.write_raw(&(data.len() as u32).to_be_bytes())?;
+            self.write_raw(data)
+        }
+
+        /// Writes the bytes run-length encoded. Each group starts with a 7-bit encoded token holding
+        /// the group length shifted left by one, with the low bit set for a run. A run is followed
+        /// by the single repeated byte, and a literal group by its bytes as-is. Literal groups hold
+        /// up to 2^29 bytes, so data without runs of three or more grows by at most five token bytes
+        /// per 2^29 bytes.
+        pub fn write_bytes_rle(&mut self, data: &[u8]) -> Result<(), BufferError> {
+            let mut literal_start = 0;
+            let mut i = 0;
+            while i < data.len() {
+                let mut run = 1;
+                while i + run < data.len() && data[i + run] == data[i] && run < RLE_MAX_GROUP {
+                    run += 1;
+                }
+                if run < 3 {
+                    i += run;
+                    continue;
+                }
+                self.write_rle_literals(&data[literal_start..i])?;
+                self.write_7bit_int(((run << 1) | 1) as i32)?;
+                self.write_u8(data[i])?;
+                i += run;
+                literal_start = i;
+            }
+            self.write_rle_literals(&data[literal_start..])
+        }
+
+        fn write_rle_literals(&mut self, literals: &[u8]) -> Result<(), BufferError> {
+            for group in literals.chunks(RLE_MAX_GROUP) {
+                self.write_7bit_int((group.len() << 1) as i32)?;
+                self.write_raw(group)?;
+            }
+            Ok(())
+        }
+
+        /// Writes the wire code the table assigns to `value` as a u32.
+        pub fn write_enum_mapped<T: Copy + PartialEq>(
+            &mut self,
+            value: T,
+            table: &[(u32, T)],
+        ) -> Result<u64, BufferError> {
+            match table.iter().find(|(_, v)| *v == value) {
+                Some((code, _)) => self.write_u32(*code),
+                None => Err(BufferError::UnmappedValue),
+            }
+        }
+
+        /// Writes the UTF-8 bytes of the string without a prefix, then `pad` bytes until at least
+        /// `min_width` bytes have been written. Longer strings are written in full.
+        pub fn write_string_min_width(
+            &mut self,
+            value: &str,
+            min_width: usize,
+            pad: u8,
+        ) -> Result<u64, BufferError> {
+            let bytes = value.as_bytes();
+            self.write_raw(bytes)?;
+            let padding = min_width.saturating_sub(bytes.len());
+            self.write_bytes(&vec![pad; padding])?;
+            Ok((bytes.len() + padding) as u64)
+        }
+
+        /// Writes a length using the minimal ASN.1 DER encoding: a single byte below 0x80,
+        /// otherwise 0x80 plus the count of big-endian length bytes that follow.
+        pub fn write_der_length(&mut self, len: u64) -> Result<u64, BufferError> {
+            if len < 0x80 {
+                return self.write_u8(len as u8);
+            }
+            let bytes = len.to_be_bytes();
+            let skip = (len.leading_zeros() / 8) as usize;
+            self.write_u8(0x80 | (8 - skip) as u8)?;
+            self.write_raw(&bytes[skip..]).map(|o| o + 1)
+        }
+
+        /// Writes `mask` as a u32, then each field whose bit index is set in the mask, in the order given.
+        /// Every set bit must have a corresponding writer.
+        pub fn write_optional_fields<O, F>(
+            &mut self,
+            mask: u32,
+            writers: &[(u32, F)],
+        ) -> Result<(), BufferError>
+        where
+            F: Fn(&mut Self) -> Result<O, BufferError>,
+        {
+            let uncovered = uncovered_bits(mask, writers);
+            if uncovered != 0 {
+                return Err(BufferError::UnexpectedFields { mask: uncovered });
+            }
+            self.write_u32(mask)?;
+            for (bit, write_fn) in writers {
+                if mask & 1u32.checked_shl(*bit).unwrap_or(0) != 0 {
+                    write_fn(self)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes a one-byte count of significant bytes followed by exactly that many
+        /// little-endian bytes of the value, so `0` takes one byte and `u64::MAX` nine.
+        pub fn write_u64_minimal(&mut self, value: u64) -> Result<u64, BufferError> {
+            let count = 8 - (value.leading_zeros() / 8) as usize;
+            self.write_u8(count as u8)?;
+            self.write_raw(&value.to_le_bytes()[..count]).map(|o| o + 1)
+        }
+
+        /// Writes a union variant as its tag, the 7-bit encoded length of its body, then the body
+        /// written by `f`, allowing readers that do not know the tag to skip it. A body longer
+        /// than `i32::MAX` bytes fails with `VecTooLong` before anything is written.
+        pub fn write_union<O, F>(&mut self, tag: u8, f: F) -> Result<O, BufferError>
+        where
+            F: FnOnce(&mut BufferWriter<Cursor<Vec<u8>>>) -> Result<O, BufferError>,
+        {
+            let mut body = BufferWriter::with_endianness(Cursor::new(Vec::new()), self.endianness);
+            let value = f(&mut body)?;
+            let body = body.writer.into_inner();
+            if body.len() > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: body.len() as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            self.write_u8(tag)?;
+            self.write_7bit_int(body.len() as i32)?;
+            self.write_bytes(&body)?;
+            Ok(value)
+        }
+
+        /// Writes a coordinate in degrees as an i32 scaled by 1e7, rounding to the nearest unit.
+        /// This keeps a precision of 1e-7 degrees, roughly 1.1cm at the equator.
+        pub fn write_coord_e7(
+            &mut self,
+            degrees: f64,
+            axis: CoordinateAxis,
+        ) -> Result<u64, BufferError> {
+            if !axis.contains(degrees) {
+                return Err(BufferError::CoordinateOutOfRange { degrees });
+            }
+            self.write_i32((degrees * 1e7).round() as i32)
+        }
+
+        /// Writes a matrix as its 7-bit encoded row and column counts followed by its elements in
+        /// the given order. `data` is always supplied in row-major order. Either count above
+        /// `i32::MAX` fails with `VecTooLong`.
+        pub fn write_matrix_f32(
+            &mut self,
+            rows: usize,
+            cols: usize,
+            data: &[f32],
+            order: MatrixOrder,
+        ) -> Result<(), BufferError> {
+            let largest = rows.max(cols);
+            if largest > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: largest as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            let expected = rows.checked_mul(cols).ok_or(BufferError::LayoutMismatch {
+                expected: usize::MAX,
+                actual: data.len(),
+            })?;
+            if expected != data.len() {
+                return Err(BufferError::LayoutMismatch {
+                    expected,
+                    actual: data.len(),
+                });
+            }
+            self.write_7bit_int(rows as i32)?;
+            self.write_7bit_int(cols as i32)?;
+            for i in 0..data.len() {
+                let index = match order {
+                    MatrixOrder::RowMajor => i,
+                    MatrixOrder::ColumnMajor => (i % rows) * cols + i / rows,
+                };
+                self.write_f32(data[index])?;
+            }
+            Ok(())
+        }
+
+        /// Writes an enum value as both its u32 code and its length-prefixed name. Older readers can
+        /// rely on the code while newer ones match on the name, so variants may be renumbered or
+        /// added without breaking either side as long as one of the two stays stable.
+        pub fn write_enum_hybrid(&mut self, code: u32, name: &str) -> Result<u64, BufferError> {
+            self.write_u32(code)?;
+            self.write_string_full(name, StringOptions::default())
+        }
+
+        /// Rounds the timestamp to the nearest multiple of `quantum_ns` and writes the number of
+        /// quanta since the previous timestamp as a zigzag 7-bit encoded integer. Reconstructed
+        /// values are off by at most half a quantum, and coarser quanta give smaller deltas.
+        /// Every timestamp in a sequence must use the same quantum.
+        pub fn write_timestamp_quantized(
+            &mut self,
+            t_ns: i64,
+            quantum_ns: i64,
+        ) -> Result<(), BufferError> {
+            let quantized = quantize(t_ns, quantum_ns)?;
+            let delta = quantized.wrapping_sub(self.last_timestamp) / quantum_ns;
+            self.last_timestamp = quantized;
+            self.write_varint_zigzag(delta)
+        }
+
+        /// Writes the bytes followed by their Adler-32 checksum as a u32.
+        pub fn write_block_adler32(&mut self, data: &[u8]) -> Result<u64, BufferError> {
+            self.write_raw(data)?;
+            self.write_u32(adler32(data))?;
+            Ok(data.len() as u64 + 4)
+        }
+
+        /// Writes a set of flag bits using the given integer width.
+        pub fn write_flags(&mut self, flags: u32, width: IntWidth) -> Result<u64, BufferError> {
+            let bits = width.bits();
+            if bits < 32 && flags >> bits != 0 {
+                return Err(BufferError::ValueTooWide {
+                    value: flags as u64,
+                    bits,
+                });
+            }
+            match width {
+                IntWidth::U8 => self.write_u8(flags as u8),
+                IntWidth::U16 => self.write_u16(flags as u16),
+                IntWidth::U32 => self.write_u32(flags),
+            }
+        }
+
+        /// Writes a typed `bitflags` set using the given integer width.
+        #[cfg(feature = "bitflags")]
+        pub fn write_bitflags<F>(&mut self, flags: F, width: IntWidth) -> Result<u64, BufferError>
+        where
+            F: bitflags::Flags<Bits = u32>,
+        {
+            self.write_flags(flags.bits(), width)
+        }
+
+        /// Writes an envelope as its u32 schema id, u16 version, u32 payload length, then the payload.
+        pub fn write_envelope(
+            &mut self,
+            schema_id: u32,
+            version: u16,
+            payload: &[u8],
+        ) -> Result<u64, BufferError> {
+            if payload.len() as u64 > u32::MAX as u64 {
+                return Err(BufferError::VecTooLong {
+                    length: payload.len() as u64,
+                    max: u32::MAX as u64,
+                });
+            }
+            self.write_u32(schema_id)?;
+            self.write_u16(version)?;
+            self.write_u32(payload.len() as u32)?;
+            self.write_raw(payload)?;
+            Ok(payload.len() as u64 + 10)
+        }
+
+        /// Writes the slices contiguously and in order, using vectored writes
+        /// so an underlying socket can emit several of them per call.
+        pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<u64, BufferError> {
+            let mut batch = WriteBatch::new();
+            for buf in bufs {
+                batch.push(buf);
+            }
+            self.write_batch(batch)
+        }
+
+        /// Writes every slice queued in the batch, contiguously and in order.
+        pub fn write_batch(&mut self, batch: WriteBatch) -> Result<u64, BufferError> {
+            let total = batch.len() as u64;
+            let mut slices = batch.slices;
+            let mut remaining = &mut slices[..];
+            // Drops leading empty slices so an empty batch doesn't look like a failed write.
+            IoSlice::advance_slices(&mut remaining, 0);
+            while !remaining.is_empty() {
+                match self.writer.write_vectored(remaining) {
+                    Ok(0) => return Err(BufferError::IOFailure),
+                    Ok(n) => IoSlice::advance_slices(&mut remaining, n),
+                    Err(e) if e.kind() == crate::io::ErrorKind::Interrupted => {}
+                    Err(_) => return Err(BufferError::IOFailure),
+                }
+            }
+            self.bytes_written += total;
+            Ok(total)
+        }
+
+        /// Writes a value in `[0.0, 1.0]` as a u16, so each step is 1/65535 (about 1.5e-5).
+        /// Out-of-range values are clamped and NaN is written as zero.
+        pub fn write_unorm16(&mut self, value: f32) -> Result<u64, BufferError> {
+            let value = if value.is_nan() {
+                0.0
+            } else {
+                value.clamp(0.0, 1.0)
+            };
+            self.write_u16((value * 65535.0).round() as u16)
+        }
+
+        /// Writes a value in `[-1.0, 1.0]` as an i16, so each step is 1/32767 (about 3.1e-5).
+        /// Out-of-range values are clamped and NaN is written as zero.
+        pub fn write_snorm16(&mut self, value: f32) -> Result<u64, BufferError> {
+            let value = if value.is_nan() {
+                0.0
+            } else {
+                value.clamp(-1.0, 1.0)
+            };
+            self.write_i16((value * 32767.0).round() as i16)
+        }
+
+        /// Writes a color as four bytes in the order red, green, blue, alpha.
+        pub fn write_rgba8(&mut self, r: u8, g: u8, b: u8, a: u8) -> Result<u64, BufferError> {
+            self.write_raw(&[r, g, b, a])
+        }
+
+        /// Writes a color packed into a single u32 with the channels arranged by `order`.
+        pub fn write_rgba8_u32(
+            &mut self,
+            r: u8,
+            g: u8,
+            b: u8,
+            a: u8,
+            order: ChannelOrder,
+        ) -> Result<u64, BufferError> {
+            let shifts = order.shifts();
+            let packed = [r, g, b, a]
+                .iter()
+                .zip(shifts.iter())
+                .fold(0u32, |acc, (&c, &s)| acc | ((c as u32) << s));
+            self.write_u32(packed)
+        }
+
+        /// Writes a unit quaternion in 32 bits using the smallest-three scheme: a 2-bit index of
+        /// the largest component, then the other three at 10 bits each. The input is assumed
+        /// to be normalized. The three stored components are reconstructed to within about 7e-4,
+        /// and the largest, which is rebuilt from them, to within about 2e-3.
+        pub fn write_quaternion_compressed(&mut self, q: [f32; 4]) -> Result<u64, BufferError> {
+            let largest = (0..4)
+                .max_by(|&a, &b| q[a].abs().total_cmp(&q[b].abs()))
+                .unwrap_or(0);
+            // q and -q are the same rotation, so flip the sign to make the dropped component positive.
+            let sign = if q[largest] < 0.0 { -1.0 } else { 1.0 };
+            let steps = ((1u32 << QUATERNION_COMPONENT_BITS) - 1) as f32;
+            let mut packed = largest as u32;
+            for (i, &component) in q.iter().enumerate() {
+                if i == largest {
+                    continue;
+                }
+                let normalized = (component * sign / QUATERNION_COMPONENT_MAX).clamp(-1.0, 1.0);
+                let quantized = ((normalized + 1.0) / 2.0 * steps).round() as u32;
+                packed = (packed << QUATERNION_COMPONENT_BITS) | quantized;
+            }
+            self.write_u32(packed)
+        }
+
+        /// Writes an ascending sequence as its count followed by the gap between each value
+        /// and the one before it, all as varints. Equal neighbours are allowed.
+        pub fn write_sorted_u64(&mut self, values: &[u64]) -> Result<(), BufferError> {
+            if let Some(index) = values.windows(2).position(|w| w[1] < w[0]) {
+                return Err(BufferError::UnsortedSequence { index: index + 1 });
+            }
+            self.write_7bit_long(values.len() as i64)?;
+            let mut previous = 0;
+            for &value in values {
+                self.write_7bit_long((value - previous) as i64)?;
+                previous = value;
+            }
+            Ok(())
+        }
+
+        /// Writes a UUID as its 16 bytes in RFC 4122 (big-endian) order.
+        #[cfg(feature = "uuid")]
+        pub fn write_uuid(&mut self, id: uuid::Uuid) -> Result<u64, BufferError> {
+            self.write_raw(id.as_bytes())
+        }
+
+        /// Writes a UUID in the mixed little-endian order used by Microsoft GUIDs.
+        #[cfg(feature = "uuid")]
+        pub fn write_uuid_le(&mut self, id: uuid::Uuid) -> Result<u64, BufferError> {
+            self.write_raw(&id.to_bytes_le())
+        }
+
+        /// Writes a RIFF/IFF style chunk: the four byte type, a u32 length in the given byte order
+        /// (little for RIFF, big for IFF), the payload, then a zero pad byte if the length is odd.
+        pub fn write_chunk(
+            &mut self,
+            fourcc: [u8; 4],
+            payload: &[u8],
+            endianness: Endianness,
+        ) -> Result<u64, BufferError> {
+            if payload.len() as u64 > u32::MAX as u64 {
+                return Err(BufferError::VecTooLong {
+                    length: payload.len() as u64,
+                    max: u32::MAX as u64,
+                });
+            }
+            let length = payload.len() as u32;
+            let prefix = match endianness {
+                Endianness::Little => length.to_le_bytes(),
+                Endianness::Big => length.to_be_bytes(),
+            };
+            let pad: &[u8] = if length % 2 == 1 { &[0] } else { &[] };
+            for part in [&fourcc[..], &prefix, payload, pad].iter() {
+                self.write_raw(part)?;
+            }
+            Ok(8 + payload.len() as u64 + pad.len() as u64)
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    impl<W: Write + Seek + Read> BufferWriter<W> {
+        /// Gets the position within the current stream.
+        pub fn position(&mut self) -> Result<u64, BufferError> {
+            self.seek(0, SeekOrigin::Current)
+        }
+
+        /// Gets the length in bytes of the stream.
+        pub fn len(&mut self) -> Result<u64, BufferError> {
+            let old_pos = self.position()?;
+            let len = self.seek(0, SeekOrigin::End)?;
+            if old_pos != len {
+                self.seek(old_pos as i64, SeekOrigin::Begin)?;
+            }
+            Ok(len)
+        }
+
+        /// Returns the full contents of the stream, leaving the current position unchanged.
+        pub fn to_vec(&mut self) -> Result<Vec<u8>, BufferError> {
+            let mut out: Vec<u8> = vec![];
+            let old_pos = self.position()?;
+            self.seek(0, SeekOrigin::Begin)?;
+            let result = self.writer.read_to_end(&mut out);
+            self.seek(old_pos as i64, SeekOrigin::Begin)?;
+            result.map_err(BufferError::ReadFailure)?;
+            Ok(out)
+        }
+
+        /// Returns the full contents of the stream as a lowercase hex string.
+        pub fn to_hex(&mut self) -> Result<String, BufferError> {
+            Ok(self
+                .to_vec()?
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect())
+        }
+
+        pub fn seek(&mut self, position: i64, origin: SeekOrigin) -> Result<u64, BufferError> {
+            if matches!(origin, SeekOrigin::Begin) && position < 0 {
+                return Err(BufferError::IndexOutOfRange { index: position });
+            }
+            match origin {
+                SeekOrigin::Begin => self.writer.seek(SeekFrom::Start(position as u64)),
+                SeekOrigin::Current => self.writer.seek(SeekFrom::Current(position)),
+                SeekOrigin::End => self.writer.seek(SeekFrom::End(position)),
+            }
+            .map_err(|_e| BufferError::IndexOutOfRange { index: position })
+        }
+
+        /// Rewinds to the start of the stream, ready to overwrite it or read it back.
+        pub fn reset(&mut self) -> Result<(), BufferError> {
+            self.seek(0, SeekOrigin::Begin).map(|_p| ())
+        }
+
+        /// Returns a hex and ASCII dump of the whole stream, for diagnosing format mismatches.
+        pub fn hex_dump(&mut self) -> Result<String, BufferError> {
+            Ok(hex_dump(&self.to_vec()?))
+        }
+
+        /// Writes a u32 length followed by the section written by `f`,
+        /// filling in the length once the section is complete. Fails with `IndexOutOfRange`
+        /// if `f` leaves the stream before the start of its section, and with `VecTooLong`
+        /// if the section doesn't fit in a u32.
+        pub fn write_length_prefixed<T, F>(&mut self, f: F) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let length_pos = self.position()?;
+            self.write_u32(0)?;
+            let value = f(self)?;
+            let end = self.position()?;
+            let section = end
+                .checked_sub(length_pos + 4)
+                .ok_or(BufferError::IndexOutOfRange { index: end as i64 })?;
+            if section > u32::MAX as u64 {
+                return Err(BufferError::VecTooLong {
+                    length: section,
+                    max: u32::MAX as u64,
+                });
+            }
+            self.seek(length_pos as i64, SeekOrigin::Begin)?;
+            self.write_u32(section as u32)?;
+            self.seek(end as i64, SeekOrigin::Begin)?;
+            Ok(value)
+        }
+
+        /// Runs `f` then appends a one-byte checksum over the bytes it wrote.
+        /// Fails with `IndexOutOfRange` if `f` leaves the stream before where it started.
+        pub fn write_with_checksum8<T, F>(
+            &mut self,
+            algorithm: Checksum8,
+            f: F,
+        ) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let start = self.position()?;
+            let value = f(self)?;
+            let end = self.position()?;
+            let length = end
+                .checked_sub(start)
+                .ok_or(BufferError::IndexOutOfRange { index: end as i64 })?;
+            let mut body = vec![0u8; length as usize];
+            self.seek(start as i64, SeekOrigin::Begin)?;
+            self.writer
+                .read_exact(&mut body)
+                .map_err(BufferError::ReadFailure)?;
+            self.write_u8(algorithm.compute(&body))?;
+            Ok(value)
+        }
+
+        /// Records the current position under `name` so it can be patched later.
+        pub fn mark_label(&mut self, name: &str) -> Result<u64, BufferError> {
+            let position = self.position()?;
+            self.labels.insert(name.to_string(), position);
+            Ok(position)
+        }
+
+        /// Overwrites the four bytes at the position recorded for `name` with a u32,
+        /// then returns to the current position.
+        pub fn patch_label_u32(&mut self, name: &str, value: u32) -> Result<(), BufferError> {
+            let offset = *self
+                .labels
+                .get(name)
+                .ok_or_else(|| BufferError::UnknownLabel {
+                    name: name.to_string(),
+                })?;
+            let current_pos = self.position()?;
+            self.seek(offset as i64, SeekOrigin::Begin)?;
+            self.write_u32(value)?;
+            self.seek(current_pos as i64, SeekOrigin::Begin)?;
+            Ok(())
+        }
+
+        /// Appends the CRC-32 of every byte in the stream so far as a u32,
+        /// so `verify_checksum` can later detect corruption.
+        pub fn write_checksum(&mut self) -> Result<u64, BufferError> {
+            let crc = crc32(&self.to_vec()?);
+            self.seek(0, SeekOrigin::End)?;
+            self.write_u32(crc)
+        }
+
+        /// Writes zero bytes until the current position is a multiple of `alignment`,
+        /// which must be a power of two.
+        pub fn align_to(&mut self, alignment: u64) -> Result<(), BufferError> {
+            let padding = padding_for(self.position()?, alignment)?;
+            self.write_bytes(&vec![0u8; padding as usize]).map(|_n| ())
+        }
+
+        /// Appends a magic marker to the end of the stream, flagging it as cleanly closed.
+        pub fn write_footer(&mut self, magic: &[u8]) -> Result<u64, BufferError> {
+            self.seek(0, SeekOrigin::End)?;
+            self.write_raw(magic)
+        }
+    }
+
+    impl BufferWriter<Cursor<Vec<u8>>> {
+        /// Creates a new BufferWriter over an empty in-memory buffer, which `to_vec` or
+        /// `into_inner` can later return.
+        pub fn new_in_memory() -> Self {
+            BufferWriter::new(Cursor::new(Vec::new()))
+        }
+    }
+
+    /// The location of a length-prefixed region that was skipped to be decoded later.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct DeferredRegion {
+        /// The position of the first byte after the length prefix.
+        pub offset: u64,
+        /// The number of bytes in the region.
+        pub length: u64,
+    }
+
+    /// The byte ranges of a stream that were never read or were read more than once.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct CoverageReport {
+        pub gaps: Vec<Range<u64>>,
+        pub overlaps: Vec<Range<u64>>,
+    }
+
+    impl CoverageReport {
+        /// Returns true when every byte was read exactly once.
+        pub fn is_complete(&self) -> bool {
+            self.gaps.is_empty() && self.overlaps.is_empty()
+        }
+    }
+
+    /// An entry in a directory of fields stored at the end of a stream.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct DirEntry {
+        pub id: u32,
+        /// The position of the field's first byte.
+        pub offset: u64,
+        /// The number of bytes in the field.
+        pub length: u64,
+    }
+
+    /// The header of a schema-tagged envelope, which is enough to decide whether to decode or skip it.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct EnvelopeHeader {
+        pub schema_id: u32,
+        pub version: u16,
+        /// The number of payload bytes that follow the header.
+        pub length: u32,
+    }
+
+    /// A payload tagged with the schema and version needed to decode it.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Envelope {
+        pub schema_id: u32,
+        pub version: u16,
+        pub length: u32,
+        pub payload: Vec<u8>,
+    }
+
+    /// A reader scoped to the bytes of a single record.
+    pub type RecordReader = BufferReader<Cursor<Vec<u8>>>;
+
+    /// Settings that control how strictly a BufferReader validates its input.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ReaderConfig {
+        /// Rejects encodings that are well-formed but not canonical, such as padded varints
+        /// or booleans other than 0 and 1.
+        pub strict: bool,
+        /// The deepest `read_nested` calls may be nested before decoding fails.
+        pub max_depth: u32,
+        /// The largest length prefix `read_vec_u32be`, `read_chunk` and `read_blob` will accept, if any.
+        pub max_vec_length: Option<u32>,
+        /// Records every byte range read so `coverage_report` can find gaps and overlaps.
+        /// Ranges are counted from where the reader started, or from the last `seek`
+        /// made through the reader. Intended for testing parsers.
+        pub track_coverage: bool,
+    }
+
+    impl Default for ReaderConfig {
+        /// A lenient config allowing 128 levels of nesting and vectors of any length.
+        fn default() -> Self {
+            ReaderConfig {
+                strict: false,
+                max_depth: 128,
+                max_vec_length: None,
+                track_coverage: false,
+            }
+        }
+    }
+
+    /// Reads primitive data types as binary values in a specific encoding.
+    pub struct BufferReader<R: Read> {
+        reader: R,
+        pub config: ReaderConfig,
+        endianness: Endianness,
+        depth: u32,
+        offset: u64,
+        coverage: Vec<Range<u64>>,
+        last_timestamp: i64,
+    }
+
+    impl<R: Read> BufferReader<R> {
+        /// Creates a new BufferReader that reads little-endian integers
+        pub fn new(reader: R) -> Self {
+            BufferReader::with_config(reader, ReaderConfig::default())
+        }
+
+        /// Creates a new BufferReader that reads integers in the given byte order
+        pub fn with_endianness(reader: R, endianness: Endianness) -> Self {
+            let mut reader = BufferReader::new(reader);
+            reader.endianness = endianness;
+            reader
+        }
+
+        /// Creates a new BufferReader using the given config
+        pub fn with_config(reader: R, config: ReaderConfig) -> Self {
+            BufferReader {
+                reader,
+                config,
+                endianness: Endianness::Little,
+                depth: 0,
+                offset: 0,
+                coverage: Vec::new(),
+                last_timestamp: 0,
+            }
+        }
+
+        /// Gets a reference to the underlying reader.
+        pub fn get_ref(&self) -> &R {
+            &self.reader
+        }
+
+        /// Gets a mutable reference to the underlying reader.
+        /// Reading or seeking it directly is not reflected in coverage tracking.
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.reader
+        }
+
+        /// Consumes the BufferReader, returning the underlying reader.
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+
+        /// Reads in a 32-bit integer in compressed format.
+        /// Encodings longer than five bytes, or that don't fit in a non-negative i32, are rejected.
+        pub fn read_7bit_int(&mut self) -> Result<i32, BufferError> {
+            let mut count: i32 = 0;
+            let mut shift = 0;
+            while {
+                // Check for a corrupted stream.  Read a max of 5 bytes.
+                if shift == 5 * 7 {
+                    // 5 bytes max per Int32, shift += 7
+                    // too many bytes in what should have been a 7 bit encoded i32.
+                    return Err(BufferError::InvalidData {
+                        message: "7-bit encoded i32 is longer than 5 bytes".to_string(),
+                    });
+                }
+                // read_u8 handles end of stream cases for us.
+                let b = self.read_u8()?;
+                check_7bit_int_byte(shift, b)?;
+                count |= ((b & 0x7F) as i32) << shift;
+                // A trailing zero byte adds nothing, so a shorter encoding would have sufficed.
+                if self.config.strict && shift > 0 && b == 0 {
+                    return Err(BufferError::NonCanonicalVarint);
+                }
+                shift += 7;
+                (b & 0x80) != 0
+            } {}
+            Ok(count)
+        }
+
+        /// Reads in a 64-bit integer in compressed format.
+        /// Encodings longer than ten bytes, or with bits past the 64th, are rejected.
+        pub fn read_7bit_long(&mut self) -> Result<i64, BufferError> {
+            let mut count: i64 = 0;
+            let mut shift = 0;
+            loop {
+                // 10 bytes max per Int64, too many bytes means a corrupted stream.
+                if shift == 10 * 7 {
+                    return Err(BufferError::InvalidData {
+                        message: "7-bit encoded i64 is longer than 10 bytes".to_string(),
+                    });
+                }
+                let b = self.read_u8()?;
+                // The tenth byte only has room for bit 63.
+                if shift == 9 * 7 && b & 0x7F > 0x01 {
+                    return Err(BufferError::InvalidData {
+                        message: format!(
+                            "7-bit encoded i64 overflows 64 bits (last byte {:#04x})",
+                            b
+                        ),
+                    });
+                }
+                count |= ((b & 0x7F) as i64) << shift;
+                if self.config.strict && shift > 0 && b == 0 {
+                    return Err(BufferError::NonCanonicalVarint);
+                }
+                shift += 7;
+                if b & 0x80 == 0 {
+                    return Ok(count);
+                }
+            }
+        }
+
+        /// Reads a signed integer written by `write_varint_zigzag`.
+        pub fn read_varint_zigzag(&mut self) -> Result<i64, BufferError> {
+            let zigzag = self.read_7bit_long()? as u64;
+            Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+        }
+
+        /// Reads a 7-bit encoded length prefix. A prefix that decodes to a negative number
+        /// fails with `InvalidData`, as `read_7bit_int` rejects it.
+        pub fn read_length(&mut self) -> Result<u64, BufferError> {
+            self.read_7bit_int().map(|length| length as u64)
+        }
+
+        /// Reads a length-prefixed string from the buffer.
+        /// A negative length prefix fails with `InvalidData`.
+        pub fn read_string(&mut self) -> Result<String, BufferError> {
+            self.read_string_full(StringOptions::default())
+        }
+
+        /// Reads a UTF-8 string written by `write_string_prefixed` with the same prefix.
+        pub fn read_string_prefixed(
+            &mut self,
+            prefix: StringPrefix,
+        ) -> Result<String, BufferError> {
+            self.read_string_full(StringOptions {
+                prefix,
+                ..StringOptions::default()
+            })
+        }
+
+        /// Reads a string laid out according to the options.
+        /// Fixed width strings have their trailing NUL padding removed.
+        pub fn read_string_full(&mut self, opts: StringOptions) -> Result<String, BufferError> {
+            let unit = code_unit_size(opts.encoding);
+            let length = match opts.prefix {
+                StringPrefix::SevenBit => Some(self.read_length()?),
+                StringPrefix::U8 => Some(self.read_u8()? as u64),
+                StringPrefix::U16 => Some(self.read_u16()? as u64),
+                StringPrefix::U32 => Some(self.read_u32()? as u64),
+                StringPrefix::Fixed(_) | StringPrefix::NullTerminated => None,
+            };
+            let bytes = match (length, opts.prefix) {
+                (Some(length), _) => {
+                    check_string_length(length, opts.max_length)?;
+                    self.read_bytes(length)?
+                }
+                (None, StringPrefix::Fixed(width)) => {
+                    let mut bytes = self.read_bytes(width as u64)?;
+                    while bytes.len() >= unit && bytes[bytes.len() - unit..].iter().all(|&b| b == 0)
+                    {
+                        bytes.truncate(bytes.len() - unit);
+                    }
+                    check_string_length(bytes.len() as u64, opts.max_length)?;
+                    bytes
+                }
+                (None, _) => {
+                    let mut bytes = Vec::new();
+                    loop {
+                        let code_unit = self.read_bytes(unit as u64)?;
+                        if code_unit.iter().all(|&b| b == 0) {
+                            break;
+                        }
+                        bytes.extend_from_slice(&code_unit);
+                        check_string_length(bytes.len() as u64, opts.max_length)?;
+                    }
+                    bytes
+                }
+            };
+            decode_string(bytes, opts.encoding)
+        }
+
+        /// Fills the buffer from the stream, recording the range read when coverage tracking is enabled.
+        /// If the stream ends first, the bytes that were available are consumed.
+        fn fill(&mut self, buffer: &mut [u8]) -> Result<(), BufferError> {
+            let filled = read_up_to(&mut self.reader, buffer)?;
+            self.advance(filled as u64);
+            check_filled(buffer.len(), filled)
+        }
+
+        /// Moves the tracked offset past `count` bytes that were just read.
+        fn advance(&mut self, count: u64) {
+            let start = self.offset;
+            self.offset += count;
+            if self.config.track_coverage && count > 0 {
+                self.coverage.push(start..self.offset);
+            }
+        }
+
+        /// Reads a length-prefixed UTF-8 string and copies it to `sink` in chunks,
+        /// validating the encoding as it goes, and returns the number of bytes copied.
+        pub fn read_string_to<S: Write>(&mut self, sink: &mut S) -> Result<u64, BufferError> {
+            let length = self.read_length()?;
+            // Room for a chunk plus up to three bytes of a character split across chunks.
+            let mut buffer = vec![0u8; STREAM_CHUNK_SIZE + 3];
+            let mut carried = 0;
+            let mut remaining = length;
+            while remaining > 0 {
+                let count = remaining.min(STREAM_CHUNK_SIZE as u64) as usize;
+                self.fill(&mut buffer[carried..carried + count])?;
+                remaining -= count as u64;
+                let data = &buffer[..carried + count];
+                let valid = match core::str::from_utf8(data) {
+                    Ok(_) => data.len(),
+                    Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                    Err(_) => return Err(BufferError::IOFailure),
+                };
+                sink.write_all(&data[..valid])
+                    .map_err(|_e| BufferError::IOFailure)?;
+                carried = data.len() - valid;
+                buffer.copy_within(valid..valid + carried, 0);
+            }
+            if carried > 0 {
+                return Err(BufferError::IOFailure);
+            }
+            Ok(length)
+        }
+
+        /// Reads a 4-byte unsigned integer from the current vector
+        /// and advances the position of the cursor by four bytes.
+        pub fn read_u32(&mut self) -> Result<u32, BufferError> {
+            let mut buffer = [0u8; 4];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => u32::from_le_bytes(buffer),
+                Endianness::Big => u32::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 8-byte unsigned integer from the current vector
+        /// and advances the position of the cursor by eight bytes.
+        pub fn read_u64(&mut self) -> Result<u64, BufferError> {
+            let mut buffer = [0u8; 8];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => u64::from_le_bytes(buffer),
+                Endianness::Big => u64::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 4-byte signed integer from the current vector
+        /// and advances the current position of the cursor by four bytes.
+        pub fn read_i32(&mut self) -> Result<i32, BufferError> {
+            let mut buffer = [0u8; 4];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => i32::from_le_bytes(buffer),
+                Endianness::Big => i32::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 2-byte unsigned integer from the current vector
+        /// and advances the position of the cursor by two bytes.
+        pub fn read_u16(&mut self) -> Result<u16, BufferError> {
+            let mut buffer = [0u8; 2];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => u16::from_le_bytes(buffer),
+                Endianness::Big => u16::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads the next byte from the current vector
+        /// and advances the current position of the cursor by one byte.
+        pub fn read_u8(&mut self) -> Result<u8, BufferError> {
+            let mut buffer = [0u8; 1];
+            self.fill(&mut buffer).map(|_b| buffer[0])
+        }
+
+        /// Reads a signed byte from the current vector
+        /// and advances the current position of the cursor by one byte.
+        pub fn read_i8(&mut self) -> Result<i8, BufferError> {
+            self.read_u8().map(|b| b as i8)
+        }
+
+        /// Reads a 2-byte signed integer from the current vector
+        /// and advances the current position of the cursor by two bytes.
+        pub fn read_i16(&mut self) -> Result<i16, BufferError> {
+            let mut buffer = [0u8; 2];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => i16::from_le_bytes(buffer),
+                Endianness::Big => i16::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads an 8-byte signed integer from the current vector
+        /// and advances the current position of the cursor by eight bytes.
+        pub fn read_i64(&mut self) -> Result<i64, BufferError> {
+            let mut buffer = [0u8; 8];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => i64::from_le_bytes(buffer),
+                Endianness::Big => i64::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 16-byte unsigned integer from the current vector
+        /// and advances the position of the cursor by sixteen bytes.
+        pub fn read_u128(&mut self) -> Result<u128, BufferError> {
+            let mut buffer = [0u8; 16];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => u128::from_le_bytes(buffer),
+                Endianness::Big => u128::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 16-byte signed integer from the current vector
+        /// and advances the position of the cursor by sixteen bytes.
+        pub fn read_i128(&mut self) -> Result<i128, BufferError> {
+            let mut buffer = [0u8; 16];
+            self.fill(&mut buffer)?;
+            Ok(match self.endianness {
+                Endianness::Little => i128::from_le_bytes(buffer),
+                Endianness::Big => i128::from_be_bytes(buffer),
+            })
+        }
+
+        /// Reads a 4-byte IEEE-754 float from the current vector
+        /// and advances the current position of the cursor by four bytes.
+        pub fn read_f32(&mut self) -> Result<f32, BufferError> {
+            self.read_u32().map(f32::from_bits)
+        }
+
+        /// Reads an 8-byte IEEE-754 float from the current vector
+        /// and advances the current position of the cursor by eight bytes.
+        pub fn read_f64(&mut self) -> Result<f64, BufferError> {
+            self.read_u64().map(f64::from_bits)
+        }
+
+        /// Reads a character written by `write_char`, failing with `InvalidData` if the value
+        /// is a surrogate or beyond the last Unicode scalar value.
+        pub fn read_char(&mut self) -> Result<char, BufferError> {
+            let value = self.read_u32()?;
+            core::char::from_u32(value).ok_or_else(|| BufferError::InvalidData {
+                message: format!("{:#x} is not a Unicode scalar value", value),
+            })
+        }
+
+        /// Reads a boolean stored as a single byte. Any nonzero byte is true, unless the reader is
+        /// strict, in which case bytes other than 0 and 1 are rejected.
+        pub fn read_bool(&mut self) -> Result<bool, BufferError> {
+            match self.read_u8()? {
+                0 => Ok(false),
+                1 => Ok(true),
+                value if self.config.strict => Err(BufferError::InvalidBool { value }),
+                _ => Ok(true),
+            }
+        }
+
+        /// Fills `buffer` exactly from the current stream without allocating, failing with
+        /// `EndOfStream` if the stream ends first.
+        pub fn read_into(&mut self, buffer: &mut [u8]) -> Result<(), BufferError> {
+            self.fill(buffer)
+        }
+
+        /// Reads the specified number of bytes from the current stream
+        /// into a byte array and advances the current position by that number of bytes.
+        /// The array grows as bytes arrive, so a count beyond the end of the stream
+        /// fails with `EndOfStream` without first allocating the whole count.
+        pub fn read_bytes(&mut self, count: u64) -> Result<Vec<u8>, BufferError> {
+            let mut buffer = Vec::new();
+            let mut remaining = count;
+            while remaining > 0 {
+                let start = buffer.len();
+                let chunk = remaining.min(STREAM_CHUNK_SIZE as u64) as usize;
+                buffer.resize(start + chunk, 0);
+                self.fill(&mut buffer[start..])
+                    .map_err(|e| chunk_error(e, count, start))?;
+                remaining -= chunk as u64;
+            }
+            Ok(buffer)
+        }
+
+        /// Reads bytes written by `write_blob`, rejecting lengths above the configured
+        /// `max_vec_length`. Like `read_bytes`, a length past the end of the stream fails
+        /// without allocating it all up front.
+        pub fn read_blob(&mut self) -> Result<Vec<u8>, BufferError> {
+            let length = self.read_length()?;
+            if let Some(max) = self.config.max_vec_length {
+                if length > max as u64 {
+                    return Err(BufferError::VecTooLong {
+                        length,
+                        max: max as u64,
+                    });
+                }
+            }
+            self.read_bytes(length)
+        }
+
+        /// Reads the next `len` bytes into a reader scoped to just that frame, so a corrupt
+        /// length inside it fails with `EndOfStream` instead of reading into adjacent fields.
+        /// The frame reader shares this reader's config and byte order.
+        pub fn read_frame(&mut self, len: u64) -> Result<RecordReader, BufferError> {
+            let frame = self.read_bytes(len)?;
+            let mut reader = BufferReader::with_config(Cursor::new(frame), self.config);
+            reader.endianness = self.endianness;
+            Ok(reader)
+        }
+
+        /// Reads a 7-bit encoded element count, rejecting counts above the configured
+        /// `max_vec_length`, then that many values with `read`. The vector grows as elements
+        /// arrive, so a count past the end of the stream fails without allocating it all up front.
+        fn read_array<T>(
+            &mut self,
+            read: fn(&mut Self) -> Result<T, BufferError>,
+        ) -> Result<Vec<T>, BufferError> {
+            let count = self.read_length()?;
+            if let Some(max) = self.config.max_vec_length {
+                if count > max as u64 {
+                    return Err(BufferError::VecTooLong {
+                        length: count,
+                        max: max as u64,
+                    });
+                }
+            }
+            let mut values = Vec::new();
+            for _ in 0..count {
+                values.push(read(self)?);
+            }
+            Ok(values)
+        }
+
+        /// Reads values written by `write_u16_array`.
+        pub fn read_u16_array(&mut self) -> Result<Vec<u16>, BufferError> {
+            self.read_array(Self::read_u16)
+        }
+
+        /// Reads values written by `write_u32_array`.
+        pub fn read_u32_array(&mut self) -> Result<Vec<u32>, BufferError> {
+            self.read_array(Self::read_u32)
+        }
+
+        /// Reads values written by `write_u64_array`.
+        pub fn read_u64_array(&mut self) -> Result<Vec<u64>, BufferError> {
+            self.read_array(Self::read_u64)
+        }
+
+        /// Reads values written by `write_i16_array`.
+        pub fn read_i16_array(&mut self) -> Result<Vec<i16>, BufferError> {
+            self.read_array(Self::read_i16)
+        }
+
+        /// Reads values written by `write_i32_array`.
+        pub fn read_i32_array(&mut self) -> Result<Vec<i32>, BufferError> {
+            self.read_array(Self::read_i32)
+        }
+
+        /// Reads values written by `write_i64_array`.
+        pub fn read_i64_array(&mut self) -> Result<Vec<i64>, BufferError> {
+            self.read_array(Self::read_i64)
+        }
+
+        /// Reads values written by `write_f32_array`.
+        pub fn read_f32_array(&mut self) -> Result<Vec<f32>, BufferError> {
+            self.read_array(Self::read_f32)
+        }
+
+        /// Reads values written by `write_f64_array`.
+        pub fn read_f64_array(&mut self) -> Result<Vec<f64>, BufferError> {
+            self.read_array(Self::read_f64)
+        }
+
+        /// Reads a value written by `write_option`, calling `f` to read it only if the presence
+        /// byte is set. The presence byte is read as by `read_bool`.
+        pub fn read_option<T, F>(&mut self, f: F) -> Result<Option<T>, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            if self.read_bool()? {
+                f(self).map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+
+        /// Reads a string written by `write_optional_string`.
+        pub fn read_optional_string(&mut self) -> Result<Option<String>, BufferError> {
+            self.read_option(Self::read_string)
+        }
+
+        /// Reads UTF-8 bytes up to the next NUL, which is consumed, or the end of the stream.
+        /// Fails with `EndOfStream` if the stream has already ended.
+        pub fn read_cstring(&mut self) -> Result<String, BufferError> {
+            let mut bytes = Vec::new();
+            loop {
+                match self.read_u8() {
+                    Ok(0) => break,
+                    Err(BufferError::EndOfStream { .. }) if !bytes.is_empty() => break,
+                    Ok(b) => bytes.push(b),
+                    Err(e) => return Err(e),
+                }
+            }
+            String::from_utf8(bytes).map_err(|e| BufferError::InvalidData {
+                message: e.to_string(),
+            })
+        }
+
+        /// Reads a field of `width` bytes and decodes it as UTF-8 once trailing `pad` and NUL
+        /// bytes are removed.
+        pub fn read_fixed_string(&mut self, width: usize, pad: u8) -> Result<String, BufferError> {
+            let mut bytes = self.read_bytes(width as u64)?;
+            while let Some(&b) = bytes.last() {
+                if b != pad && b != 0 {
+                    break;
+                }
+                bytes.pop();
+            }
+            String::from_utf8(bytes).map_err(|e| BufferError::InvalidData {
+                message: e.to_string(),
+            })
+        }
+
+        /// Reads a string written by `write_string_utf16`, failing with `InvalidData` if it
+        /// contains an unpaired surrogate.
+        pub fn read_string_utf16(&mut self) -> Result<String, BufferError> {
+            let length = self.read_length()?;
+            let mut units = Vec::new();
+            for _ in 0..length {
+                units.push(self.read_u16()?);
+            }
+            core::char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|e| BufferError::InvalidData {
+                    message: format!("unpaired surrogate {:#06x}", e.unpaired_surrogate()),
+                })
+        }
+
+        /// Reads every byte from the current position to the end of the stream.
+        /// This works on any source, as it reads until the source reports it has finished.
+        pub fn read_to_end(&mut self) -> Result<Vec<u8>, BufferError> {
+            let mut buffer = Vec::new();
+            self.reader
+                .read_to_end(&mut buffer)
+                .map_err(BufferError::ReadFailure)?;
+            self.advance(buffer.len() as u64);
+            Ok(buffer)
+        }
+
+        /// Advances past `count` bytes without keeping them, failing with `EndOfStream` if the
+        /// stream ends first. The bytes are read and discarded so this works on any source;
+        /// skipped bytes are not counted as read by coverage tracking.
+        pub fn skip(&mut self, count: u64) -> Result<(), BufferError> {
+            let mut scratch = [0u8; STREAM_CHUNK_SIZE];
+            let mut skipped = 0;
+            while skipped < count {
+                let chunk = (count - skipped).min(STREAM_CHUNK_SIZE as u64) as usize;
+                let filled = read_up_to(&mut self.reader, &mut scratch[..chunk])?;
+                skipped += filled as u64;
+                if filled < chunk {
+                    break;
+                }
+            }
+            self.offset += skipped;
+            if skipped < count {
+                return Err(BufferError::EndOfStream {
+                    needed: count,
+                    available: skipped,
+                });
+            }
+            Ok(())
+        }
+
+        /// Reads a packed bitfield described by the layout, most significant bit first,
+        /// and returns each field's value in order. Trailing bits that do not fill
+        /// a whole byte are consumed and discarded.
+        pub fn read_bitfields(&mut self, layout: &[BitField]) -> Result<Vec<u64>, BufferError> {
+            let data = self.read_bytes(bitfield_size(layout)?)?;
+            let mut values = Vec::with_capacity(layout.len());
+            let mut bit = 0usize;
+            for field in layout {
+                let mut value = 0u64;
+                for _ in 0..field.bits {
+                    let set = (data[bit / 8] >> (7 - bit % 8)) & 1;
+                    value = (value << 1) | set as u64;
+                    bit += 1;
+                }
+                values.push(value);
+            }
+            Ok(values)
+        }
+
+        /// Reads a sparse array written by `write_sparse`, filling unset elements with `T::default()`.
+        /// The logical length isn't backed by bytes in the stream, so it fails with `VecTooLong`
+        /// above `max_len` or the configured `max_vec_length`. The array grows as entries arrive
+        /// and is only padded out to its full length once every entry has been read.
+        pub fn read_sparse<T, F>(
+            &mut self,
+            max_len: usize,
+            mut read_fn: F,
+        ) -> Result<Vec<T>, BufferError>
+        where
+            T: Default + Clone,
+            F: FnMut(&mut Self) -> Result<T, BufferError>,
+        {
+            let len = self.read_length()?;
+            let max = match self.config.max_vec_length {
+                Some(limit) => (limit as u64).min(max_len as u64),
+                None => max_len as u64,
+            };
+            if len > max {
+                return Err(BufferError::VecTooLong { length: len, max });
+            }
+            let len = len as usize;
+            let count = self.read_length()?;
+            let mut values = Vec::new();
+            for _ in 0..count {
+                let index = self.read_length()? as usize;
+                if index < values.len() || index >= len {
+                    return Err(BufferError::InvalidSparseIndex { index });
+                }
+                let value = read_fn(self)?;
+                values.resize(index, T::default());
+                values.push(value);
+            }
+            values.resize(len, T::default());
+            Ok(values)
+        }
+
+        /// Reads a value written by `write_delta_u32`, returning `previous` when it was unchanged.
+        pub fn read_delta_u32(&mut self, previous: u32) -> Result<u32, BufferError> {
+            match self.read_u8()? {
+                0 => Ok(previous),
+                _ => self.read_u32(),
+            }
+        }
+
+        /// Reads a u32 written by `write_optional_sentinel_u32`, mapping `sentinel` back to `None`.
+        pub fn read_optional_sentinel_u32(
+            &mut self,
+            sentinel: u32,
+        ) -> Result<Option<u32>, BufferError> {
+            let value = self.read_u32()?;
+            Ok(if value == sentinel { None } else { Some(value) })
+        }
+
+        /// Runs `f` one level deeper in a recursive decode,
+        /// failing if that would exceed the configured maximum depth.
+        pub fn read_nested<T, F>(&mut self, f: F) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            if self.depth >= self.config.max_depth {
+                return Err(BufferError::DepthExceeded {
+                    max_depth: self.config.max_depth,
+                });
+            }
+            self.depth += 1;
+            let result = f(self);
+            self.depth -= 1;
+            result
+        }
+
+        /// Reads bytes prefixed by their length as a big-endian u32,
+        /// rejecting lengths above the configured `max_vec_length`.
+        pub fn read_vec_u32be(&mut self) -> Result<Vec<u8>, BufferError> {
+            let prefix = self.read_bytes(4)?;
+            let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+            if let Some(max) = self.config.max_vec_length {
+                if length > max {
+                    return Err(BufferError::VecTooLong {
+                        length: length as u64,
+                        max: max as u64,
+                    });
+                }
+            }
+            self.read_bytes(length as u64)
+        }
+
+        /// Reads bytes written by `write_bytes_rle`, ensuring they decode to exactly `expected_len` bytes.
+        pub fn read_bytes_rle(&mut self, expected_len: usize) -> Result<Vec<u8>, BufferError> {
+            let mut out = Vec::with_capacity(expected_len);
+            while out.len() < expected_len {
+                let token = self.read_length()?;
+                let group = (token >> 1) as usize;
+                if group == 0 {
+                    return Err(BufferError::IOFailure);
+                }
+                if out.len() + group > expected_len {
+                    return Err(BufferError::LengthMismatch {
+                        declared: expected_len as u64,
+                        actual: (out.len() + group) as u64,
+                    });
+                }
+                if token & 1 == 1 {
+                    let value = self.read_u8()?;
+                    out.resize(out.len() + group, value);
+                } else {
+                    out.extend(self.read_bytes(group as u64)?);
+                }
+            }
+            Ok(out)
+        }
+
+        /// Reads a u32 wire code and returns the value the table maps it to.
+        pub fn read_enum_mapped<T: Copy>(&mut self, table: &[(u32, T)]) -> Result<T, BufferError> {
+            let tag = self.read_u32()?;
+            table
+                .iter()
+                .find(|(code, _)| *code == tag)
+                .map(|(_, value)| *value)
+                .ok_or(BufferError::InvalidTag { tag })
+        }
+
+        /// Reads a UTF-8 string occupying exactly `width` bytes and strips any trailing `trim` bytes.
+        pub fn read_string_trimmed(
+            &mut self,
+            width: usize,
+            trim: u8,
+        ) -> Result<String, BufferError> {
+            let mut bytes = self.read_bytes(width as u64)?;
+            while bytes.last() == Some(&trim) {
+                bytes.pop();
+            }
+            String::from_utf8(bytes).map_err(|_e| BufferError::IOFailure)
+        }
+
+        /// Reads an ASN.1 DER length, rejecting the indefinite form and non-minimal encodings.
+        pub fn read_der_length(&mut self) -> Result<u64, BufferError> {
+            let first = self.read_u8()?;
+            if first < 0x80 {
+                return Ok(first as u64);
+            }
+            let count = (first & 0x7F) as u64;
+            if count == 0 || count > 8 {
+                return Err(BufferError::InvalidDerLength);
+            }
+            let bytes = self.read_bytes(count)?;
+            if bytes[0] == 0 {
+                return Err(BufferError::InvalidDerLength);
+            }
+            let len = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            if len < 0x80 {
+                return Err(BufferError::InvalidDerLength);
+            }
+            Ok(len)
+        }
+
+        /// Reads each field of a fixed-layout record in order and returns the decoded values.
+        pub fn read_record(
+            &mut self,
+            fields: &[RecordField],
+        ) -> Result<Vec<RecordValue>, BufferError> {
+            let mut values = Vec::with_capacity(fields.len());
+            for field in fields {
+                let size = match field.kind {
+                    RecordType::U8 | RecordType::I8 => 1,
+                    RecordType::U16 | RecordType::I16 => 2,
+                    RecordType::U32 | RecordType::I32 | RecordType::F32 => 4,
+                    RecordType::U64 | RecordType::I64 | RecordType::F64 => 8,
+                    RecordType::Bytes(count) => {
+                        values.push(RecordValue::Bytes(self.read_bytes(count as u64)?));
+                        continue;
+                    }
+                };
+                let mut bytes = self.read_bytes(size)?;
+                if field.endianness != Some(Endianness::Big) {
+                    bytes.reverse();
+                }
+                let raw = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+                values.push(match field.kind {
+                    RecordType::U8 => RecordValue::U8(raw as u8),
+                    RecordType::U16 => RecordValue::U16(raw as u16),
+                    RecordType::U32 => RecordValue::U32(raw as u32),
+                    RecordType::U64 => RecordValue::U64(raw),
+                    RecordType::I8 => RecordValue::I8(raw as i8),
+                    RecordType::I16 => RecordValue::I16(raw as i16),
+                    RecordType::I32 => RecordValue::I32(raw as i32),
+                    RecordType::I64 => RecordValue::I64(raw as i64),
+                    RecordType::F32 => RecordValue::F32(f32::from_bits(raw as u32)),
+                    RecordType::F64 => RecordValue::F64(f64::from_bits(raw)),
+                    RecordType::Bytes(_) => unreachable!(),
+                });
+            }
+            Ok(values)
+        }
+
+        /// Reads a u32 presence mask, then decodes each field whose bit index is set using its reader.
+        /// Returns one entry per reader, `None` for fields that were absent.
+        pub fn read_optional_fields<T, F>(
+            &mut self,
+            readers: &[(u32, F)],
+        ) -> Result<Vec<Option<T>>, BufferError>
+        where
+            F: Fn(&mut Self) -> Result<T, BufferError>,
+        {
+            let mask = self.read_u32()?;
+            let uncovered = uncovered_bits(mask, readers);
+            if uncovered != 0 {
+                return Err(BufferError::UnexpectedFields { mask: uncovered });
+            }
+            let mut values = Vec::with_capacity(readers.len());
+            for (bit, read_fn) in readers {
+                if mask & 1u32.checked_shl(*bit).unwrap_or(0) != 0 {
+                    values.push(Some(read_fn(self)?));
+                } else {
+                    values.push(None);
+                }
+            }
+            Ok(values)
+        }
+
+        /// Reads a u64 written by `write_u64_minimal`.
+        pub fn read_u64_minimal(&mut self) -> Result<u64, BufferError> {
+            let count = self.read_u8()?;
+            if count > 8 {
+                return Err(BufferError::InvalidIntegerLength { length: count });
+            }
+            let bytes = self.read_bytes(count as u64)?;
+            Ok(bytes
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+        }
+
+        /// Reads a coordinate written by `write_coord_e7` and returns it in degrees.
+        pub fn read_coord_e7(&mut self, axis: CoordinateAxis) -> Result<f64, BufferError> {
+            let degrees = self.read_i32()? as f64 / 1e7;
+            if !axis.contains(degrees) {
+                return Err(BufferError::CoordinateOutOfRange { degrees });
+            }
+            Ok(degrees)
+        }
+
+        /// Reads a matrix written by `write_matrix_f32` and returns its dimensions
+        /// and elements in row-major order.
+        pub fn read_matrix_f32(
+            &mut self,
+            order: MatrixOrder,
+        ) -> Result<(usize, usize, Vec<f32>), BufferError> {
+            let rows = self.read_length()? as usize;
+            let cols = self.read_length()? as usize;
+            let count = rows
+                .checked_mul(cols)
+                .ok_or_else(|| BufferError::InvalidData {
+                    message: format!("{}x{} matrix has too many elements", rows, cols),
+                })?;
+            // Elements are read before any are placed, so a corrupt count fails at the end of the
+            // stream instead of allocating up front.
+            let mut stored = Vec::new();
+            for _ in 0..count {
+                stored.push(self.read_f32()?);
+            }
+            let data = match order {
+                MatrixOrder::RowMajor => stored,
+                MatrixOrder::ColumnMajor => (0..count)
+                    .map(|index| stored[(index % cols) * rows + index / cols])
+                    .collect(),
+            };
+            Ok((rows, cols, data))
+        }
+
+        /// Reads an enum value written by `write_enum_hybrid` as its code and name,
+        /// leaving the caller to decide which to trust.
+        pub fn read_enum_hybrid(&mut self) -> Result<(u32, String), BufferError> {
+            let code = self.read_u32()?;
+            let name = self.read_string()?;
+            Ok((code, name))
+        }
+
+        /// Reads a timestamp written by `write_timestamp_quantized` using the same quantum,
+        /// returning the quantized value in nanoseconds.
+        pub fn read_timestamp_quantized(&mut self, quantum_ns: i64) -> Result<i64, BufferError> {
+            if quantum_ns <= 0 {
+                return Err(BufferError::InvalidQuantum { quantum_ns });
+            }
+            let delta = self.read_varint_zigzag()?;
+            self.last_timestamp = self
+                .last_timestamp
+                .wrapping_add(delta.wrapping_mul(quantum_ns));
+            Ok(self.last_timestamp)
+        }
+
+        /// Reads `len` bytes followed by their Adler-32 checksum, verifying the bytes match it.
+        pub fn read_block_adler32(&mut self, len: u64) -> Result<Vec<u8>, BufferError> {
+            let data = self.read_bytes(len)?;
+            let expected = self.read_u32()?;
+            let actual = adler32(&data);
+            if expected != actual {
+                return Err(BufferError::ChecksumMismatch { expected, actual });
+            }
+            Ok(data)
+        }
+
+        /// Reads a set of flag bits stored with the given integer width.
+        pub fn read_flags(&mut self, width: IntWidth) -> Result<u32, BufferError> {
+            match width {
+                IntWidth::U8 => self.read_u8().map(|v| v as u32),
+                IntWidth::U16 => self.read_u16().map(|v| v as u32),
+                IntWidth::U32 => self.read_u32(),
+            }
+        }
+
+        /// Reads a typed `bitflags` set stored with the given integer width,
+        /// rejecting bits that do not correspond to a defined flag.
+        #[cfg(feature = "bitflags")]
+        pub fn read_bitflags<F>(&mut self, width: IntWidth) -> Result<F, BufferError>
+        where
+            F: bitflags::Flags<Bits = u32>,
+        {
+            let bits = self.read_flags(width)?;
+            F::from_bits(bits).ok_or(BufferError::UnexpectedFields {
+                mask: bits & !F::all().bits(),
+            })
+        }
+
+        /// Reads only the header of an envelope, leaving the position at the start of its payload.
+        pub fn read_envelope_header(&mut self) -> Result<EnvelopeHeader, BufferError> {
+            Ok(EnvelopeHeader {
+                schema_id: self.read_u32()?,
+                version: self.read_u16()?,
+                length: self.read_u32()?,
+            })
+        }
+
+        /// Reads an envelope written by `write_envelope`.
+        pub fn read_envelope(&mut self) -> Result<Envelope, BufferError> {
+            let header = self.read_envelope_header()?;
+            Ok(Envelope {
+                schema_id: header.schema_id,
+                version: header.version,
+                length: header.length,
+                payload: self.read_bytes(header.length as u64)?,
+            })
+        }
+
+        /// Reads a value written by `write_unorm16`.
+        pub fn read_unorm16(&mut self) -> Result<f32, BufferError> {
+            Ok(self.read_u16()? as f32 / 65535.0)
+        }
+
+        /// Reads a value written by `write_snorm16`. The unused code -32768 also decodes as -1.0.
+        pub fn read_snorm16(&mut self) -> Result<f32, BufferError> {
+            Ok((self.read_i16()? as f32 / 32767.0).max(-1.0))
+        }
+
+        /// Reads a color written by `write_rgba8` as `(r, g, b, a)`.
+        pub fn read_rgba8(&mut self) -> Result<(u8, u8, u8, u8), BufferError> {
+            let mut buffer = [0u8; 4];
+            self.fill(&mut buffer)?;
+            Ok((buffer[0], buffer[1], buffer[2], buffer[3]))
+        }
+
+        /// Reads a color written by `write_rgba8_u32` with the same `order`, as `(r, g, b, a)`.
+        pub fn read_rgba8_u32(
+            &mut self,
+            order: ChannelOrder,
+        ) -> Result<(u8, u8, u8, u8), BufferError> {
+            let packed = self.read_u32()?;
+            let [r, g, b, a] = order.shifts().map(|s| (packed >> s) as u8);
+            Ok((r, g, b, a))
+        }
+
+        /// Reads a quaternion written by `write_quaternion_compressed`, reconstructing the largest
+        /// component from the other three.
+        pub fn read_quaternion_compressed(&mut self) -> Result<[f32; 4], BufferError> {
+            let packed = self.read_u32()?;
+            let largest = (packed >> (3 * QUATERNION_COMPONENT_BITS)) as usize;
+            let mask = (1u32 << QUATERNION_COMPONENT_BITS) - 1;
+            let steps = mask as f32;
+            let mut q = [0f32; 4];
+            let mut shift = 3 * QUATERNION_COMPONENT_BITS;
+            let mut sum = 0f32;
+            for (i, component) in q.iter_mut().enumerate() {
+                if i == largest {
+                    continue;
+                }
+                shift -= QUATERNION_COMPONENT_BITS;
+                let quantized = (packed >> shift) & mask;
+                *component = (quantized as f32 / steps * 2.0 - 1.0) * QUATERNION_COMPONENT_MAX;
+                sum += *component * *component;
+            }
+            q[largest] = (1.0 - sum).max(0.0).sqrt();
+            Ok(q)
+        }
+
+        /// Reads a sequence written by `write_sorted_u64`.
+        pub fn read_sorted_u64(&mut self) -> Result<Vec<u64>, BufferError> {
+            let count = self.read_7bit_long()? as u64;
+            let mut values = Vec::new();
+            let mut previous = 0u64;
+            for index in 0..count as usize {
+                previous = previous
+                    .checked_add(self.read_7bit_long()? as u64)
+                    .ok_or(BufferError::UnsortedSequence { index })?;
+                values.push(previous);
+            }
+            Ok(values)
+        }
+
+        /// Reads a UUID written by `write_uuid`.
+        #[cfg(feature = "uuid")]
+        pub fn read_uuid(&mut self) -> Result<uuid::Uuid, BufferError> {
+            let mut buffer = [0u8; 16];
+            self.fill(&mut buffer)?;
+            Ok(uuid::Uuid::from_bytes(buffer))
+        }
+
+        /// Reads a UUID written by `write_uuid_le`.
+        #[cfg(feature = "uuid")]
+        pub fn read_uuid_le(&mut self) -> Result<uuid::Uuid, BufferError> {
+            let mut buffer = [0u8; 16];
+            self.fill(&mut buffer)?;
+            Ok(uuid::Uuid::from_bytes_le(buffer))
+        }
+
+        /// Reads a chunk written by `write_chunk` as its type and payload, consuming any pad byte.
+        pub fn read_chunk(
+            &mut self,
+            endianness: Endianness,
+        ) -> Result<(Vec<u8>, Vec<u8>), BufferError> {
+            let fourcc = self.read_bytes(4)?;
+            let mut prefix = [0u8; 4];
+            self.fill(&mut prefix)?;
+            let length = match endianness {
+                Endianness::Little => u32::from_le_bytes(prefix),
+                Endianness::Big => u32::from_be_bytes(prefix),
+            };
+            if let Some(max) = self.config.max_vec_length {
+                if length > max {
+                    return Err(BufferError::VecTooLong {
+                        length: length as u64,
+                        max: max as u64,
+                    });
+                }
+            }
+            let payload = self.read_bytes(length as u64)?;
+            if length % 2 == 1 {
+                self.read_u8()?;
+            }
+            Ok((fourcc, payload))
+        }
+    }
+
+    impl<R: Read + Seek> BufferReader<R> {
+        /// Gets the position within the current stream.
+        pub fn position(&mut self) -> Result<u64, BufferError> {
+            self.seek(0, SeekOrigin::Current)
+        }
+
+        /// Gets the length in bytes of the stream.
+        pub fn len(&mut self) -> Result<u64, BufferError> {
+            let old_pos = self.position()?;
+            let len = self.seek(0, SeekOrigin::End)?;
+            if old_pos != len {
+                self.seek(old_pos as i64, SeekOrigin::Begin)?;
+            }
+            Ok(len)
+        }
+
+        /// Gets the number of bytes between the current position and the end of the stream.
+        pub fn remaining(&mut self) -> Result<u64, BufferError> {
+            let position = self.position()?;
+            Ok(self.len()?.saturating_sub(position))
+        }
+
+        /// Checks whether every byte of the stream has been read.
+        pub fn is_empty(&mut self) -> Result<bool, BufferError> {
+            Ok(self.remaining()? == 0)
+        }
+
+        pub fn seek(&mut self, position: i64, origin: SeekOrigin) -> Result<u64, BufferError> {
+            if matches!(origin, SeekOrigin::Begin) && position < 0 {
+                return Err(BufferError::IndexOutOfRange { index: position });
+            }
+            self.offset = match origin {
+                SeekOrigin::Begin => self.reader.seek(SeekFrom::Start(position as u64)),
+                SeekOrigin::Current => self.reader.seek(SeekFrom::Current(position)),
+                SeekOrigin::End => self.reader.seek(SeekFrom::End(position)),
+            }
+            .map_err(|_e| BufferError::IndexOutOfRange { index: position })?;
+            Ok(self.offset)
+        }
+
+        /// Returns a hex and ASCII dump of the whole stream without advancing the current position.
+        /// The dumped bytes are not counted as read by coverage tracking.
+        pub fn hex_dump(&mut self) -> Result<String, BufferError> {
+            let mut data = Vec::new();
+            let old_pos = self.position()?;
+            self.seek(0, SeekOrigin::Begin)?;
+            let result = self.reader.read_to_end(&mut data);
+            self.seek(old_pos as i64, SeekOrigin::Begin)?;
+            result.map_err(BufferError::ReadFailure)?;
+            Ok(hex_dump(&data))
+        }
+
+        /// Rewinds to the start of the stream.
+        pub fn reset(&mut self) -> Result<(), BufferError> {
+            self.seek(0, SeekOrigin::Begin).map(|_p| ())
+        }
+
+        /// Returns the next byte without advancing the current position.
+        /// At the end of the stream this fails with `EndOfStream` and consumes nothing.
+        pub fn peek_u8(&mut self) -> Result<u8, BufferError> {
+            let mut buffer = [0u8; 1];
+            if read_up_to(&mut self.reader, &mut buffer)? == 0 {
+                return Err(BufferError::EndOfStream {
+                    needed: 1,
+                    available: 0,
+                });
+            }
+            self.seek(-1, SeekOrigin::Current)?;
+            Ok(buffer[0])
+        }
+
+        /// Reads the specified number of bytes at a pointer from the current stream
+        /// into a byte array without advancing the current position.
+        pub fn read_bytes_at(&mut self, offset: u64, count: u64) -> Result<Vec<u8>, BufferError> {
+            let len = self.len()?;
+            if offset.checked_add(count).is_none_or(|end| end > len) {
+                return Err(BufferError::EndOfStream {
+                    needed: count,
+                    available: len.saturating_sub(offset),
+                });
+            }
+            let current_pos = self.position()?;
+            self.seek(offset as i64, SeekOrigin::Begin)?;
+            let buffer = self.read_bytes(count)?;
+            self.seek(current_pos as i64, SeekOrigin::Begin)?;
+            Ok(buffer)
+        }
+
+        /// Seeks to `offset`, runs `read` and then returns to the original position,
+        /// even if the read failed.
+        fn read_at<T>(
+            &mut self,
+            offset: u64,
+            read: fn(&mut Self) -> Result<T, BufferError>,
+        ) -> Result<T, BufferError> {
+            let current_pos = self.position()?;
+            self.seek(offset as i64, SeekOrigin::Begin)?;
+            let result = read(self);
+            self.seek(current_pos as i64, SeekOrigin::Begin)?;
+            result
+        }
+
+        /// Reads a u16 in the configured byte order at `offset` without advancing the current position.
+        pub fn read_u16_at(&mut self, offset: u64) -> Result<u16, BufferError> {
+            self.read_at(offset, Self::read_u16)
+        }
+
+        /// Reads a u32 in the configured byte order at `offset` without advancing the current position.
+        pub fn read_u32_at(&mut self, offset: u64) -> Result<u32, BufferError> {
+            self.read_at(offset, Self::read_u32)
+        }
+
+        /// Reads a u64 in the configured byte order at `offset` without advancing the current position.
+        pub fn read_u64_at(&mut self, offset: u64) -> Result<u64, BufferError> {
+            self.read_at(offset, Self::read_u64)
+        }
+
+        /// Ensures the current position is a multiple of `alignment`.
+        pub fn assert_aligned(&mut self, alignment: u64) -> Result<(), BufferError> {
+            let position = self.position()?;
+            if alignment == 0 || !position.is_multiple_of(alignment) {
+                return Err(BufferError::Misaligned {
+                    position,
+                    alignment,
+                });
+            }
+            Ok(())
+        }
+
+        /// Runs `f` and returns its result along with the number of bytes it advanced the position.
+        pub fn measured<T, F>(&mut self, f: F) -> Result<(T, u64), BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let start = self.position()?;
+            let value = f(self)?;
+            let end = self.position()?;
+            Ok((value, end.saturating_sub(start)))
+        }
+
+        /// Reads a u32 length then runs `f`, ensuring it consumed exactly the declared number of bytes.
+        pub fn read_length_prefixed<T, F>(&mut self, f: F) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let declared = self.read_u32()? as u64;
+            let (value, actual) = self.measured(f)?;
+            if declared != actual {
+                return Err(BufferError::LengthMismatch { declared, actual });
+            }
+            Ok(value)
+        }
+
+        /// Runs `f` then reads a one-byte checksum and verifies it against the bytes `f` consumed.
+        pub fn read_with_checksum8<T, F>(
+            &mut self,
+            algorithm: Checksum8,
+            f: F,
+        ) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let start = self.position()?;
+            let (value, consumed) = self.measured(f)?;
+            let actual = algorithm.compute(&self.read_bytes_at(start, consumed)?);
+            let expected = self.read_u8()?;
+            if expected != actual {
+                return Err(BufferError::ChecksumMismatch {
+                    expected: expected as u32,
+                    actual: actual as u32,
+                });
+            }
+            Ok(value)
+        }
+
+        /// Reads the record at `index` from a stream of fixed-size records without advancing the current position.
+        pub fn record_at(&mut self, record_size: u64, index: u64) -> Result<Vec<u8>, BufferError> {
+            let offset = index
+                .checked_mul(record_size)
+                .ok_or(BufferError::EndOfStream {
+                    needed: record_size,
+                    available: 0,
+                })?;
+            self.read_bytes_at(offset, record_size)
+        }
+
+        /// Gets the number of whole fixed-size records in the stream.
+        pub fn record_count(&mut self, record_size: u64) -> Result<u64, BufferError> {
+            Ok(self.len()?.checked_div(record_size).unwrap_or(0))
+        }
+
+        /// Reads a u32 length and ensures it matches the number of bytes from the start of that
+        /// length field to the end of the stream. When `includes_length_field` is false the
+        /// declared length is expected to exclude the four bytes of the field itself.
+        pub fn validate_self_length_u32(
+            &mut self,
+            includes_length_field: bool,
+        ) -> Result<(), BufferError> {
+            let start = self.position()?;
+            let declared = self.read_u32()? as u64;
+            let mut actual = self.len()? - start;
+            if !includes_length_field {
+                actual -= 4;
+            }
+            if declared != actual {
+                return Err(BufferError::LengthMismatch { declared, actual });
+            }
+            Ok(())
+        }
+
+        /// Reads a union variant written by `write_union`, decoding it with the reader registered
+        /// for its tag. Variants with unknown tags are skipped and `None` is returned.
+        pub fn read_union<T, F>(&mut self, known: &[(u8, F)]) -> Result<Option<T>, BufferError>
+        where
+            F: Fn(&mut Self) -> Result<T, BufferError>,
+        {
+            let tag = self.read_u8()?;
+            let declared = self.read_length()?;
+            match known.iter().find(|(t, _)| *t == tag) {
+                Some((_, read_fn)) => {
+                    let (value, actual) = self.measured(read_fn)?;
+                    if declared != actual {
+                        return Err(BufferError::LengthMismatch { declared, actual });
+                    }
+                    Ok(Some(value))
+                }
+                None => {
+                    let available = self.remaining()?;
+                    if declared > available {
+                        return Err(BufferError::EndOfStream {
+                            needed: declared,
+                            available,
+                        });
+                    }
+                    self.seek(declared as i64, SeekOrigin::Current)?;
+                    Ok(None)
+                }
+            }
+        }
+
+        /// Reads a 7-bit length prefix and skips over the region it describes,
+        /// returning a handle that can be decoded later with `decode_deferred`.
+        pub fn read_deferred(&mut self) -> Result<DeferredRegion, BufferError> {
+            let length = self.read_length()?;
+            let offset = self.position()?;
+            let available = self.len()?.saturating_sub(offset);
+            if length > available {
+                return Err(BufferError::EndOfStream {
+                    needed: length,
+                    available,
+                });
+            }
+            self.seek(length as i64, SeekOrigin::Current)?;
+            Ok(DeferredRegion { offset, length })
+        }
+
+        /// Seeks to a deferred region and decodes it with `f`, then returns to the current position.
+        pub fn decode_deferred<T, F>(
+            &mut self,
+            region: DeferredRegion,
+            f: F,
+        ) -> Result<T, BufferError>
+        where
+            F: FnOnce(&mut Self) -> Result<T, BufferError>,
+        {
+            let current_pos = self.position()?;
+            self.seek(region.offset as i64, SeekOrigin::Begin)?;
+            let result = self.measured(f);
+            self.seek(current_pos as i64, SeekOrigin::Begin)?;
+            let (value, actual) = result?;
+            if actual > region.length {
+                return Err(BufferError::LengthMismatch {
+                    declared: region.length,
+                    actual,
+                });
+            }
+            Ok(value)
+        }
+
+        /// Lists the ranges of the stream that were never read, or were read more than once,
+        /// since coverage tracking was enabled.
+        pub fn coverage_report(&mut self) -> Result<CoverageReport, BufferError> {
+            let mut ranges = self.coverage.clone();
+            ranges.sort_by_key(|r| (r.start, r.end));
+            let mut report = CoverageReport::default();
+            let mut covered = 0;
+            for range in ranges {
+                if range.start > covered {
+                    report.gaps.push(covered..range.start);
+                } else if range.start < covered {
+                    report.overlaps.push(range.start..range.end.min(covered));
+                }
+                covered = covered.max(range.end);
+            }
+            let len = self.len()?;
+            if covered < len {
+                report.gaps.push(covered..len);
+            }
+            Ok(report)
+        }
+
+        /// Reads a directory beginning at `entry_count_offset` without advancing the current position.
+        /// The directory is a u32 entry count followed by each entry's u32 id, u64 offset and u64 length.
+        /// Every entry must lie within the stream.
+        pub fn read_directory(
+            &mut self,
+            entry_count_offset: u64,
+        ) -> Result<Vec<DirEntry>, BufferError> {
+            let len = self.len()?;
+            let current_pos = self.position()?;
+            self.seek(entry_count_offset as i64, SeekOrigin::Begin)?;
+            let result = (|| {
+                let count = self.read_u32()? as u64;
+                let available = len.saturating_sub(self.position()?);
+                if count * 20 > available {
+                    return Err(BufferError::EndOfStream {
+                        needed: count * 20,
+                        available,
+                    });
+                }
+                let mut entries = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let entry = DirEntry {
+                        id: self.read_u32()?,
+                        offset: self.read_u64()?,
+                        length: self.read_u64()?,
+                    };
+                    match entry.offset.checked_add(entry.length) {
+                        Some(end) if end <= len => entries.push(entry),
+                        _ => {
+                            return Err(BufferError::EndOfStream {
+                                needed: entry.length,
+                                available: len.saturating_sub(entry.offset),
+                            })
+                        }
+                    }
+                }
+                Ok(entries)
+            })();
+            self.seek(current_pos as i64, SeekOrigin::Begin)?;
+            result
+        }
+
+        /// Checks the u32 CRC-32 appended by `write_checksum` against the bytes before it,
+        /// without advancing the current position.
+        pub fn verify_checksum(&mut self) -> Result<(), BufferError> {
+            let len = self.len()?;
+            if len < 4 {
+                return Err(BufferError::EndOfStream {
+                    needed: 4,
+                    available: len,
+                });
+            }
+            let expected = self.read_u32_at(len - 4)?;
+            let actual = crc32(&self.read_bytes_at(0, len - 4)?);
+            if expected != actual {
+                return Err(BufferError::ChecksumMismatch { expected, actual });
+            }
+            Ok(())
+        }
+
+        /// Skips padding until the current position is a multiple of `alignment`,
+        /// which must be a power of two. The padding bytes are not checked.
+        pub fn align_to(&mut self, alignment: u64) -> Result<(), BufferError> {
+            let padding = padding_for(self.position()?, alignment)?;
+            self.skip(padding)
+        }
+
+        /// Checks whether the stream ends with the given magic marker
+        /// without advancing the current position.
+        pub fn has_valid_footer(&mut self, magic: &[u8]) -> Result<bool, BufferError> {
+            let len = self.len()?;
+            let size = magic.len() as u64;
+            if size > len {
+                return Ok(false);
+            }
+            Ok(self.read_bytes_at(len - size, size)? == magic)
+        }
+
+        /// Reads length-prefixed records until the end of the stream, handing each one to `f`
+        /// as its own scoped reader built by `read_frame`. Only a single record is held in memory
+        /// at a time, and record lengths above the configured `max_vec_length` are rejected.
+        pub fn for_each_record<F>(&mut self, mut f: F) -> Result<(), BufferError>
+        where
+            F: FnMut(RecordReader) -> Result<(), BufferError>,
+        {
+            while self.position()? < self.len()? {
+                let record_length = self.read_length()?;
+                if let Some(max) = self.config.max_vec_length {
+                    if record_length > max as u64 {
+                        return Err(BufferError::VecTooLong {
+                            length: record_length,
+                            max: max as u64,
+                        });
+                    }
+                }
+                f(self.read_frame(record_length)?)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl BufferReader<Cursor<Vec<u8>>> {
+        /// Creates a new BufferReader over an owned buffer.
+        pub fn from_bytes(data: Vec<u8>) -> Self {
+            BufferReader::new(Cursor::new(data))
+        }
+
+        /// Creates a new BufferReader over the bytes described by a hex string.
+        pub fn from_hex(s: &str) -> Result<Self, BufferError> {
+            if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(BufferError::InvalidHex);
+            }
+            let data = (0..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_e| BufferError::InvalidHex))
+                .collect::<Result<Vec<u8>, BufferError>>()?;
+            Ok(BufferReader::new(Cursor::new(data)))
+        }
+
+        /// Creates an independent reader over a copy of the data, positioned at the same offset.
+        pub fn fork(&mut self) -> Result<Self, BufferError> {
+            let mut cursor = Cursor::new(self.reader.get_ref().clone());
+            cursor.set_position(self.reader.position());
+            let mut fork = BufferReader::with_config(cursor, self.config);
+            fork.endianness = self.endianness;
+            fork.offset = self.offset;
+            Ok(fork)
+        }
+
+        /// Creates an independent reader over a copy of the data, positioned at `offset`, leaving
+        /// this reader where it is. The whole buffer is copied, so to read a single field of a
+        /// large buffer `read_u32_at` and friends avoid the cost.
+        pub fn fork_at(&mut self, offset: u64) -> Result<Self, BufferError> {
+            let mut fork = self.fork()?;
+            fork.seek(offset as i64, SeekOrigin::Begin)?;
+            Ok(fork)
+        }
+    }
+
+    impl<'a> BufferReader<Cursor<&'a [u8]>> {
+        /// Creates a new BufferReader over a borrowed buffer, without copying it.
+        pub fn from_slice(data: &'a [u8]) -> Self {
+            BufferReader::new(Cursor::new(data))
+        }
+    }
+
+    /// Interns strings written to a stream. The first occurrence of a string is written in full
+    /// and assigned the next index; later occurrences are written as just that index.
+    #[derive(Debug, Default)]
+    pub struct StringTableWriter {
+        indices: BTreeMap<String, u32>,
+    }
+
+    impl StringTableWriter {
+        /// Creates a new, empty StringTableWriter
+        pub fn new() -> Self {
+            StringTableWriter::default()
+        }
+
+        /// Writes a 7-bit encoded marker, zero for a new entry followed by the string itself,
+        /// or one more than the index of a previously written string.
+        pub fn write_interned<W>(
+            &mut self,
+            writer: &mut BufferWriter<W>,
+            s: &str,
+        ) -> Result<(), BufferError>
+        where
+            W: Write,
+        {
+            if let Some(index) = self.indices.get(s) {
+                return writer.write_7bit_int(*index as i32 + 1);
+            }
+            writer.write_7bit_int(0)?;
+            writer.write_string_full(s, StringOptions::default())?;
+            let index = self.indices.len() as u32;
+            self.indices.insert(s.to_string(), index);
+            Ok(())
+        }
+    }
+
+    /// Reconstructs strings written by a StringTableWriter.
+    #[derive(Debug, Default)]
+    pub struct StringTableReader {
+        strings: Vec<String>,
+    }
+
+    impl StringTableReader {
+        /// Creates a new, empty StringTableReader
+        pub fn new() -> Self {
+            StringTableReader::default()
+        }
+
+        /// Reads a string written by `StringTableWriter::write_interned`.
+        pub fn read_interned<R>(
+            &mut self,
+            reader: &mut BufferReader<R>,
+        ) -> Result<String, BufferError>
+        where
+            R: Read,
+        {
+            match reader.read_length()? {
+                0 => {
+                    let s = reader.read_string()?;
+                    self.strings.push(s.clone());
+                    Ok(s)
+                }
+                marker => self
+                    .strings
+                    .get(marker as usize - 1)
+                    .cloned()
+                    .ok_or(BufferError::UnknownStringIndex { index: marker - 1 }),
+            }
+        }
+    }
+
+    /// The number of bits needed to store any value in `[min, max]`.
+    fn range_bits(min: i64, max: i64) -> u32 {
+        let span = (max as i128 - min as i128) as u128;
+        128 - span.leading_zeros()
+    }
+
+    /// Packs values into a stream bit by bit, most significant bit first.
+    /// Call `finish` to write out any partially filled final byte.
+    pub struct BitWriter<'a, W: Write> {
+        writer: &'a mut BufferWriter<W>,
+        pending: u8,
+        used: u32,
+    }
+
+    impl<'a, W: Write> BitWriter<'a, W> {
+        /// Creates a new BitWriter that writes whole bytes to `writer`
+        pub fn new(writer: &'a mut BufferWriter<W>) -> Self {
+            BitWriter {
+                writer,
+                pending: 0,
+                used: 0,
+            }
+        }
+
+        /// Writes the low `bits` bits of `value`.
+        pub fn write_bits(&mut self, value: u64, bits: u32) -> Result<(), BufferError> {
+            if bits > 64 || (bits < 64 && value >> bits != 0) {
+                return Err(BufferError::ValueTooWide { value, bits });
+            }
+            for i in (0..bits).rev() {
+                self.pending |= (((value >> i) & 1) as u8) << (7 - self.used);
+                self.used += 1;
+                if self.used == 8 {
+                    self.writer.write_u8(self.pending)?;
+                    self.pending = 0;
+                    self.used = 0;
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes a value known to lie in `[min, max]` as `value - min` in the fewest bits
+        /// that can hold the whole range. A range of a single value takes no bits at all.
+        pub fn write_ranged(&mut self, value: i64, min: i64, max: i64) -> Result<(), BufferError> {
+            if min > max || value < min || value > max {
+                return Err(BufferError::ValueOutOfRange { value, min, max });
+            }
+            let offset = (value as i128 - min as i128) as u64;
+            self.write_bits(offset, range_bits(min, max))
+        }
+
+        /// Pads any partially filled byte with zero bits and writes it.
+        pub fn finish(mut self) -> Result<(), BufferError> {
+            if self.used > 0 {
+                self.writer.write_u8(self.pending)?;
+                self.used = 0;
+            }
+            Ok(())
+        }
+    }
+
+    /// Unpacks values written by a BitWriter.
+    pub struct BitReader<'a, R: Read> {
+        reader: &'a mut BufferReader<R>,
+        current: u8,
+        remaining: u32,
+    }
+
+    impl<'a, R: Read> BitReader<'a, R> {
+        /// Creates a new BitReader that reads whole bytes from `reader`
+        pub fn new(reader: &'a mut BufferReader<R>) -> Self {
+            BitReader {
+                reader,
+                current: 0,
+                remaining: 0,
+            }
+        }
+
+        /// Reads `bits` bits as the low bits of a u64.
+        pub fn read_bits(&mut self, bits: u32) -> Result<u64, BufferError> {
+            if bits > 64 {
+                return Err(BufferError::ValueTooWide { value: 0, bits });
+            }
+            let mut value = 0u64;
+            for _ in 0..bits {
+                if self.remaining == 0 {
+                    self.current = self.reader.read_u8()?;
+                    self.remaining = 8;
+                }
+                self.remaining -= 1;
+                value = (value << 1) | ((self.current >> self.remaining) & 1) as u64;
+            }
+            Ok(value)
+        }
+
+        /// Reads a value written by `BitWriter::write_ranged` with the same bounds.
+        pub fn read_ranged(&mut self, min: i64, max: i64) -> Result<i64, BufferError> {
+            if min > max {
+                return Err(BufferError::ValueOutOfRange {
+                    value: min,
+                    min,
+                    max,
+                });
+            }
+            let offset = self.read_bits(range_bits(min, max))?;
+            let value = min as i128 + offset as i128;
+            if value > max as i128 {
+                return Err(BufferError::ValueOutOfRange {
+                    value: value as i64,
+                    min,
+                    max,
+                });
+            }
+            Ok(value as i64)
+        }
+    }
+
+    /// A type that can be written to a stream with a `BufferWriter`.
+    /// Implementations for structs usually write each field in turn.
+    pub trait BufferSerialize {
+        fn write_to<W: Write>(&self, writer: &mut BufferWriter<W>) -> Result<(), BufferError>;
+    }
+
+    /// A type that can be read back from a stream with a `BufferReader`.
+    /// Implementations should read fields in the same order `BufferSerialize` wrote them.
+    pub trait BufferDeserialize: Sized {
+        fn read_from<R: Read>(reader: &mut BufferReader<R>) -> Result<Self, BufferError>;
+    }
+
+    /// Implements both traits for a primitive using its `BufferWriter` and `BufferReader` methods.
+    macro_rules! impl_buffer_primitive {
+        ($($ty:ty => $write:ident, $read:ident;)*) => {
+            $(
+                impl BufferSerialize for $ty {
+                    fn write_to<W: Write>(
+                        &self,
+                        writer: &mut BufferWriter<W>,
+                    ) -> Result<(), BufferError> {
+                        writer.$write(*self).map(|_n| ())
+                    }
+                }
+
+                impl BufferDeserialize for $ty {
+                    fn read_from<R: Read>(reader: &mut BufferReader<R>) -> Result<Self, BufferError> {
+                        reader.$read()
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_buffer_primitive! {
+        u8 => write_u8, read_u8;
+        u16 => write_u16, read_u16;
+        u32 => write_u32, read_u32;
+        u64 => write_u64, read_u64;
+        u128 => write_u128, read_u128;
+        i8 => write_i8, read_i8;
+        i16 => write_i16, read_i16;
+        i32 => write_i32, read_i32;
+        i64 => write_i64, read_i64;
+        i128 => write_i128, read_i128;
+        f32 => write_f32, read_f32;
+        f64 => write_f64, read_f64;
+        bool => write_bool, read_bool;
+    }
+
+    impl BufferSerialize for String {
+        /// Writes the string length-prefixed, as `write_string` does.
+        fn write_to<W: Write>(&self, writer: &mut BufferWriter<W>) -> Result<(), BufferError> {
+            writer
+                .write_string_full(self, StringOptions::default())
+                .map(|_n| ())
+        }
+    }
+
+    impl BufferDeserialize for String {
+        fn read_from<R: Read>(reader: &mut BufferReader<R>) -> Result<Self, BufferError> {
+            reader.read_string()
+        }
+    }
+
+    impl<T: BufferSerialize> BufferSerialize for Vec<T> {
+        /// Writes the 7-bit encoded element count, then each element.
+        fn write_to<W: Write>(&self, writer: &mut BufferWriter<W>) -> Result<(), BufferError> {
+            if self.len() > i32::MAX as usize {
+                return Err(BufferError::VecTooLong {
+                    length: self.len() as u64,
+                    max: i32::MAX as u64,
+                });
+            }
+            writer.write_7bit_int(self.len() as i32)?;
+            for value in self.iter() {
+                value.write_to(writer)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<T: BufferDeserialize> BufferDeserialize for Vec<T> {
+        /// Reads an element count and that many elements, with the same `max_vec_length`
+        /// limit as the primitive array reads.
+        fn read_from<R: Read>(reader: &mut BufferReader<R>) -> Result<Self, BufferError> {
+            reader.read_array(T::read_from)
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum BufferError {
+        #[error("seek index ({index}) was out of range. Must be non-negative and less than the size of the collection.")]
+        IndexOutOfRange { index: i64 },
+        #[error(
+            "attempted to read {needed} bytes past the end of a stream with {available} available."
+        )]
+        EndOfStream { needed: u64, available: u64 },
+        #[error("unable to read bytes from buffer: {0:?}")]
+        ReadFailure(#[from] crate::io::Error),
+        #[error("unable to write data to buffer.")]
+        IOFailure,
+        #[error("stream data is malformed: {message}.")]
+        InvalidData { message: String },
+        #[error("bit field `{name}` has an invalid width of {bits} bits.")]
+        InvalidBitWidth { name: String, bits: u32 },
+        #[error("value ({value}) does not fit in bit field `{name}`.")]
+        BitFieldOverflow { name: String, value: u64 },
+        #[error("layout describes {expected} fields but {actual} values were given.")]
+        LayoutMismatch { expected: usize, actual: usize },
+        #[error("character {ch:?} cannot be represented in the requested string layout.")]
+        UnrepresentableChar { ch: char },
+        #[error("string length ({length}) exceeds the maximum of {max} bytes.")]
+        StringTooLong { length: usize, max: usize },
+        #[error("7-bit encoded integer was not in its shortest form.")]
+        NonCanonicalVarint,
+        #[error("sparse index ({index}) was out of range or out of order.")]
+        InvalidSparseIndex { index: usize },
+        #[error("input was not a valid hex string.")]
+        InvalidHex,
+        #[error("position ({position}) is not aligned to {alignment} bytes.")]
+        Misaligned { position: u64, alignment: u64 },
+        #[error("value ({value}) collides with the sentinel used for absent values.")]
+        SentinelCollision { value: u32 },
+        #[error("declared length ({declared}) does not match the actual length ({actual}).")]
+        LengthMismatch { declared: u64, actual: u64 },
+        #[error("nesting exceeded the maximum depth of {max_depth}.")]
+        DepthExceeded { max_depth: u32 },
+        #[error("vector length ({length}) exceeds the maximum of {max} bytes.")]
+        VecTooLong { length: u64, max: u64 },
+        #[error("tag ({tag}) does not match any known value.")]
+        InvalidTag { tag: u32 },
+        #[error("value has no wire code in the mapping table.")]
+        UnmappedValue,
+        #[error("DER length was indefinite, non-minimal or too large.")]
+        InvalidDerLength,
+        #[error("checksum mismatch: stored {expected:#x} but computed {actual:#x}.")]
+        ChecksumMismatch { expected: u32, actual: u32 },
+        #[error("no label named `{name}` has been marked.")]
+        UnknownLabel { name: String },
+        #[error("presence mask bits ({mask:#x}) have no corresponding field.")]
+        UnexpectedFields { mask: u32 },
+        #[error("integer declared {length} bytes, more than the 8 allowed.")]
+        InvalidIntegerLength { length: u8 },
+        #[error("coordinate ({degrees}) is out of range.")]
+        CoordinateOutOfRange { degrees: f64 },
+        #[error("quantum ({quantum_ns}ns) must be positive.")]
+        InvalidQuantum { quantum_ns: i64 },
+        #[error("interned string index ({index}) has not been defined.")]
+        UnknownStringIndex { index: u64 },
+        #[error("value ({value}) does not fit in {bits} bits.")]
+        ValueTooWide { value: u64, bits: u32 },
+        #[error("sequence value at index {index} is out of ascending order.")]
+        UnsortedSequence { index: usize },
+        #[error("value ({value}) is outside the range [{min}, {max}].")]
+        ValueOutOfRange { value: i64, min: i64, max: i64 },
+        #[error("byte ({value}) is not a valid boolean.")]
+        InvalidBool { value: u8 },
+    }
+
+    impl Clone for BufferError {
+        /// Clones the error. A `ReadFailure` is rebuilt from the kind and message of the
+        /// original `io::Error`, as its source error cannot be cloned.
+        fn clone(&self) -> Self {
+            match self {
+                BufferError::IndexOutOfRange { index } => {
+                    BufferError::IndexOutOfRange { index: *index }
+                }
+                BufferError::EndOfStream { needed, available } => BufferError::EndOfStream {
+                    needed: *needed,
+                    available: *available,
+                },
+                BufferError::ReadFailure(error) => {
+                    BufferError::ReadFailure(crate::io::Error::new(error.kind(), error.to_string()))
+                }
+                BufferError::IOFailure => BufferError::IOFailure,
+                BufferError::InvalidData { message } => BufferError::InvalidData {
+                    message: message.clone(),
+                },
+                BufferError::InvalidBitWidth { name, bits } => BufferError::InvalidBitWidth {
+                    name: name.clone(),
+                    bits: *bits,
+                },
+                BufferError::BitFieldOverflow { name, value } => BufferError::BitFieldOverflow {
+                    name: name.clone(),
+                    value: *value,
+                },
+                BufferError::LayoutMismatch { expected, actual } => BufferError::LayoutMismatch {
+                    expected: *expected,
+                    actual: *actual,
+                },
+                BufferError::UnrepresentableChar { ch } => {
+                    BufferError::UnrepresentableChar { ch: *ch }
+                }
+                BufferError::StringTooLong { length, max } => BufferError::StringTooLong {
+                    length: *length,
+                    max: *max,
+                },
+                BufferError::NonCanonicalVarint => BufferError::NonCanonicalVarint,
+                BufferError::InvalidSparseIndex { index } => {
+                    BufferError::InvalidSparseIndex { index: *index }
+                }
+                BufferError::InvalidHex => BufferError::InvalidHex,
+                BufferError::Misaligned {
+                    position,
+                    alignment,
+                } => BufferError::Misaligned {
+                    position: *position,
+                    alignment: *alignment,
+                },
+                BufferError::SentinelCollision { value } => {
+                    BufferError::SentinelCollision { value: *value }
+                }
+                BufferError::LengthMismatch { declared, actual } => BufferError::LengthMismatch {
+                    declared: *declared,
+                    actual: *actual,
+                },
+                BufferError::DepthExceeded { max_depth } => BufferError::DepthExceeded {
+                    max_depth: *max_depth,
+                },
+                BufferError::VecTooLong { length, max } => BufferError::VecTooLong {
+                    length: *length,
+                    max: *max,
+                },
+                BufferError::InvalidTag { tag } => BufferError::InvalidTag { tag: *tag },
+                BufferError::UnmappedValue => BufferError::UnmappedValue,
+                BufferError::InvalidDerLength => BufferError::InvalidDerLength,
+                BufferError::ChecksumMismatch { expected, actual } => {
+                    BufferError::ChecksumMismatch {
+                        expected: *expected,
+                        actual: *actual,
+                    }
+                }
+                BufferError::UnknownLabel { name } => {
+                    BufferError::UnknownLabel { name: name.clone() }
+                }
+                BufferError::UnexpectedFields { mask } => {
+                    BufferError::UnexpectedFields { mask: *mask }
+                }
+                BufferError::InvalidIntegerLength { length } => {
+                    BufferError::InvalidIntegerLength { length: *length }
+                }
+                BufferError::CoordinateOutOfRange { degrees } => {
+                    BufferError::CoordinateOutOfRange { degrees: *degrees }
+                }
+                BufferError::InvalidQuantum { quantum_ns } => BufferError::InvalidQuantum {
+                    quantum_ns: *quantum_ns,
+                },
+                BufferError::UnknownStringIndex { index } => {
+                    BufferError::UnknownStringIndex { index: *index }
+                }
+                BufferError::ValueTooWide { value, bits } => BufferError::ValueTooWide {
+                    value: *value,
+                    bits: *bits,
+                },
+                BufferError::UnsortedSequence { index } => {
+                    BufferError::UnsortedSequence { index: *index }
+                }
+                BufferError::ValueOutOfRange { value, min, max } => BufferError::ValueOutOfRange {
+                    value: *value,
+                    min: *min,
+                    max: *max,
+                },
+                BufferError::InvalidBool { value } => BufferError::InvalidBool { value: *value },
+            }
+        }
+    }
+
+    impl PartialEq for BufferError {
+        /// Compares variants and their fields. Two `ReadFailure` errors are equal when
+        /// their `io::ErrorKind` matches.
+        fn eq(&self, other: &Self) -> bool {
+            match (self, other) {
+                (
+                    BufferError::IndexOutOfRange { index: a_index },
+                    BufferError::IndexOutOfRange { index: b_index },
+                ) => a_index == b_index,
+                (
+                    BufferError::EndOfStream {
+                        needed: a_needed,
+                        available: a_available,
+                    },
+                    BufferError::EndOfStream {
+                        needed: b_needed,
+                        available: b_available,
+                    },
+                ) => a_needed == b_needed && a_available == b_available,
+                (BufferError::ReadFailure(a), BufferError::ReadFailure(b)) => a.kind() == b.kind(),
+                (BufferError::IOFailure, BufferError::IOFailure) => true,
+                (
+                    BufferError::InvalidData { message: a_message },
+                    BufferError::InvalidData { message: b_message },
+                ) => a_message == b_message,
+                (
+                    BufferError::InvalidBitWidth {
+                        name: a_name,
+                        bits: a_bits,
+                    },
+                    BufferError::InvalidBitWidth {
+                        name: b_name,
+                        bits: b_bits,
+                    },
+                ) => a_name == b_name && a_bits == b_bits,
+                (
+                    BufferError::BitFieldOverflow {
+                        name: a_name,
+                        value: a_value,
+                    },
+                    BufferError::BitFieldOverflow {
+                        name: b_name,
+                        value: b_value,
+                    },
+                ) => a_name == b_name && a_value == b_value,
+                (
+                    BufferError::LayoutMismatch {
+                        expected: a_expected,
+                        actual: a_actual,
+                    },
+                    BufferError::LayoutMismatch {
+                        expected: b_expected,
+                        actual: b_actual,
+                    },
+                ) => a_expected == b_expected && a_actual == b_actual,
+                (
+                    BufferError::UnrepresentableChar { ch: a_ch },
+                    BufferError::UnrepresentableChar { ch: b_ch },
+                ) => a_ch == b_ch,
+                (
+                    BufferError::StringTooLong {
+                        length: a_length,
+                        max: a_max,
+                    },
+                    BufferError::StringTooLong {
+                        length: b_length,
+                        max: b_max,
+                    },
+                ) => a_length == b_length && a_max == b_max,
+                (BufferError::NonCanonicalVarint, BufferError::NonCanonicalVarint) => true,
+                (
+                    BufferError::InvalidSparseIndex { index: a_index },
+                    BufferError::InvalidSparseIndex { index: b_index },
+                ) => a_index == b_index,
+                (BufferError::InvalidHex, BufferError::InvalidHex) => true,
+                (
+                    BufferError::Misaligned {
+                        position: a_position,
+                        alignment: a_alignment,
+                    },
+                    BufferError::Misaligned {
+                        position: b_position,
+                        alignment: b_alignment,
+                    },
+                ) => a_position == b_position && a_alignment == b_alignment,
+                (
+                    BufferError::SentinelCollision { value: a_value },
+                    BufferError::SentinelCollision { value: b_value },
+                ) => a_value == b_value,
+                (
+                    BufferError::LengthMismatch {
+                        declared: a_declared,
+                        actual: a_actual,
+                    },
+                    BufferError::LengthMismatch {
+                        declared: b_declared,
+                        actual: b_actual,
+                    },
+                ) => a_declared == b_declared && a_actual == b_actual,
+                (
+                    BufferError::DepthExceeded {
+                        max_depth: a_max_depth,
+                    },
+                    BufferError::DepthExceeded {
+                        max_depth: b_max_depth,
+                    },
+                ) => a_max_depth == b_max_depth,
+                (
+                    BufferError::VecTooLong {
+                        length: a_length,
+                        max: a_max,
+                    },
+                    BufferError::VecTooLong {
+                        length: b_length,
+                        max: b_max,
+                    },
+                ) => a_length == b_length && a_max == b_max,
+                (
+                    BufferError::InvalidTag { tag: a_tag },
+                    BufferError::InvalidTag { tag: b_tag },
+                ) => a_tag == b_tag,
+                (BufferError::UnmappedValue, BufferError::UnmappedValue) => true,
+                (BufferError::InvalidDerLength, BufferError::InvalidDerLength) => true,
+                (
+                    BufferError::ChecksumMismatch {
+                        expected: a_expected,
+                        actual: a_actual,
+                    },
+                    BufferError::ChecksumMismatch {
+                        expected: b_expected,
+                        actual: b_actual,
+                    },
+                ) => a_expected == b_expected && a_actual == b_actual,
+                (
+                    BufferError::UnknownLabel { name: a_name },
+                    BufferError::UnknownLabel { name: b_name },
+                ) => a_name == b_name,
+                (
+                    BufferError::UnexpectedFields { mask: a_mask },
+                    BufferError::UnexpectedFields { mask: b_mask },
+                ) => a_mask == b_mask,
+                (
+                    BufferError::InvalidIntegerLength { length: a_length },
+                    BufferError::InvalidIntegerLength { length: b_length },
+                ) => a_length == b_length,
+                (
+                    BufferError::CoordinateOutOfRange { degrees: a_degrees },
+                    BufferError::CoordinateOutOfRange { degrees: b_degrees },
+                ) => a_degrees == b_degrees,
+                (
+                    BufferError::InvalidQuantum {
+                        quantum_ns: a_quantum_ns,
+                    },
+                    BufferError::InvalidQuantum {
+                        quantum_ns: b_quantum_ns,
+                    },
+                ) => a_quantum_ns == b_quantum_ns,
+                (
+                    BufferError::UnknownStringIndex { index: a_index },
+                    BufferError::UnknownStringIndex { index: b_index },
+                ) => a_index == b_index,
+                (
+                    BufferError::ValueTooWide {
+                        value: a_value,
+                        bits: a_bits,
+                    },
+                    BufferError::ValueTooWide {
+                        value: b_value,
+                        bits: b_bits,
+                    },
+                ) => a_value == b_value && a_bits == b_bits,
+                (
+                    BufferError::UnsortedSequence { index: a_index },
+                    BufferError::UnsortedSequence { index: b_index },
+                ) => a_index == b_index,
+                (
+                    BufferError::ValueOutOfRange {
+                        value: a_value,
+                        min: a_min,
+                        max: a_max,
+                    },
+                    BufferError::ValueOutOfRange {
+                        value: b_value,
+                        min: b_min,
+                        max: b_max,
+                    },
+                ) => a_value == b_value && a_min == b_min && a_max == b_max,
+                (
+                    BufferError::InvalidBool { value: a_value },
+                    BufferError::InvalidBool { value: b_value },
+                ) => a_value == b_value,
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub mod async_buffer;
+#[cfg(feature = "bytes")]
+pub mod bytes_cursor;
+#[cfg(feature = "std")]
+pub mod decompress;
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    #[test]
+    fn it_works() {
+        use crate::buffer::{BufferReader, BufferWriter, SeekOrigin};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u32(9002).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.seek(0, SeekOrigin::Begin).unwrap();
+        buffer.write_u32(9003).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(9003, reader.read_u32().unwrap());
+        assert_eq!(9002, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+    }
+
+    #[test]
+    fn for_each_record() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let count = 10_000u32;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for i in 0..count {
+            let padding = vec![0xAA; (i % 8) as usize];
+            buffer.write_7bit_int(4 + padding.len() as i32).unwrap();
+            buffer.write_u32(i).unwrap();
+            buffer.write_bytes(&padding).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        let mut visited = 0u32;
+        reader
+            .for_each_record(|mut record| {
+                assert_eq!(4 + (visited % 8) as u64, record.len()?);
+                assert_eq!(visited, record.read_u32()?);
+                record.skip((visited % 8) as u64)?;
+                assert!(record.is_empty()?);
+                visited += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, visited);
+
+        // Record readers inherit the config, and the record length limit applies.
+        let config = ReaderConfig {
+            strict: true,
+            max_vec_length: Some(11),
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data.clone()), config);
+        reader
+            .for_each_record(|record| {
+                assert_eq!(config, record.config);
+                Ok(())
+            })
+            .unwrap();
+        let config = ReaderConfig {
+            max_vec_length: Some(10),
+            ..config
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data), config);
+        assert!(matches!(
+            reader.for_each_record(|_record| Ok(())),
+            Err(BufferError::VecTooLong {
+                length: 11,
+                max: 10
+            })
+        ));
+    }
+
+    #[test]
+    fn read_length_rejects_negative() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let mut reader = BufferReader::new(Cursor::new(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]));
+        assert!(matches!(
+            reader.read_length(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]));
+        assert!(matches!(
+            reader.read_string(),
+            Err(BufferError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn footer() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let magic = b"DONE";
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("log entry".to_string()).unwrap();
+        buffer.write_footer(magic).unwrap();
+        let data = buffer.to_vec().unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(reader.has_valid_footer(magic).unwrap());
+        assert_eq!(0, reader.position().unwrap());
+        assert_eq!(9001, reader.read_u32().unwrap());
+
+        let truncated = data[..data.len() - 1].to_vec();
+        let mut reader = BufferReader::new(Cursor::new(truncated));
+        assert!(!reader.has_valid_footer(magic).unwrap());
+        let mut reader = BufferReader::new(Cursor::new(vec![1u8]));
+        assert!(!reader.has_valid_footer(magic).unwrap());
+    }
+
+    #[test]
+    fn bitfields() {
+        use crate::buffer::{BitField, BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        const LAYOUT: &[BitField] = &[
+            BitField::new("version", 4),
+            BitField::new("flags", 12),
+            BitField::new("length", 16),
+        ];
+        let mut reader = BufferReader::new(Cursor::new(vec![0xAB, 0xCD, 0x12, 0x34]));
+        assert_eq!(
+            vec![0xA, 0xBCD, 0x1234],
+            reader.read_bitfields(LAYOUT).unwrap()
+        );
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer
+            .write_bitfields(LAYOUT, &[0xA, 0xBCD, 0x1234])
+            .unwrap();
+        assert_eq!(vec![0xAB, 0xCD, 0x12, 0x34], buffer.to_vec().unwrap());
+
+        let partial = &[BitField::new("a", 3), BitField::new("b", 2)];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_bitfields(partial, &[0b101, 0b11]).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![0b1011_1000], data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(vec![0b101, 0b11], reader.read_bitfields(partial).unwrap());
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.write_bitfields(partial, &[0b1000, 0]),
+            Err(BufferError::BitFieldOverflow { .. })
+        ));
+        assert!(matches!(
+            buffer.write_bitfields(&[BitField::new("wide", 65)], &[0]),
+            Err(BufferError::InvalidBitWidth { bits: 65, .. })
+        ));
+    }
+
+    #[test]
+    fn string_options() {
+        use crate::buffer::{
+            BufferError, BufferReader, BufferWriter, StringEncoding, StringOptions, StringPrefix,
+        };
+        use std::io::Cursor;
+        let utf16 = StringOptions {
+            prefix: StringPrefix::U16,
+            encoding: StringEncoding::Utf16Le,
+            max_length: None,
+        };
+        let cstring = StringOptions {
+            prefix: StringPrefix::NullTerminated,
+            encoding: StringEncoding::Ascii,
+            max_length: None,
+        };
+        let fixed = StringOptions {
+            prefix: StringPrefix::Fixed(8),
+            ..StringOptions::default()
+        };
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(6, buffer.write_string_full("héy", utf16).unwrap());
+        assert_eq!(5, buffer.write_string_full("hello", cstring).unwrap());
+        assert_eq!(4, buffer.write_string_full("abcd", fixed).unwrap());
+        buffer.write_string("plain".to_string()).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(8 + 6 + 8 + 6, data.len());
+        assert_eq!(&[6, 0, b'h', 0, 0xE9, 0, b'y', 0], &data[..8]);
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!("héy", reader.read_string_full(utf16).unwrap());
+        assert_eq!("hello", reader.read_string_full(cstring).unwrap());
+        assert_eq!("abcd", reader.read_string_full(fixed).unwrap());
+        assert_eq!("plain", reader.read_string().unwrap());
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.write_string_full("é", cstring),
+            Err(BufferError::UnrepresentableChar { ch: 'é' })
+        ));
+        assert!(matches!(
+            buffer.write_string_full("a\0b", cstring),
+            Err(BufferError::UnrepresentableChar { ch: '\0' })
+        ));
+        let byte_prefix = StringOptions {
+            prefix: StringPrefix::U8,
+            ..StringOptions::default()
+        };
+        assert!(matches!(
+            buffer.write_string_full(&"x".repeat(256), byte_prefix),
+            Err(BufferError::StringTooLong {
+                length: 256,
+                max: 255
+            })
+        ));
+        assert_eq!(8, buffer.write_string_full("too long", fixed).unwrap());
+        assert!(matches!(
+            buffer.write_string_full("far too long", fixed),
+            Err(BufferError::StringTooLong { length: 12, max: 8 })
+        ));
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_string("a long string".to_string()).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        let capped = StringOptions {
+            max_length: Some(4),
+            ..StringOptions::default()
+        };
+        assert!(matches!(
+            reader.read_string_full(capped),
+            Err(BufferError::StringTooLong { length: 13, max: 4 })
+        ));
+    }
+
+    #[test]
+    fn strict_varint() {
+        use crate::buffer::{BufferError, BufferReader, ReaderConfig};
+        use std::io::Cursor;
+        let strict = ReaderConfig {
+            strict: true,
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(vec![0x80, 0x00]), strict);
+        assert!(matches!(
+            reader.read_7bit_int(),
+            Err(BufferError::NonCanonicalVarint)
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0x80, 0x00]));
+        assert_eq!(0, reader.read_7bit_int().unwrap());
+        let mut reader = BufferReader::with_config(Cursor::new(vec![0x80, 0x01, 0x00]), strict);
+        assert_eq!(128, reader.read_7bit_int().unwrap());
+        assert_eq!(0, reader.read_7bit_int().unwrap());
+    }
+
+    #[test]
+    fn sparse() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let entries = [(3usize, 7u32), (500, 9001), (999, 42)];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer
+            .write_sparse(1000, &entries, |w, v| w.write_u32(*v))
+            .unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert!(data.len() < 32);
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        let values = reader.read_sparse(1000, |r| r.read_u32()).unwrap();
+        assert_eq!(1000, values.len());
+        assert_eq!(7, values[3]);
+        assert_eq!(9001, values[500]);
+        assert_eq!(42, values[999]);
+        assert_eq!(9001 + 7 + 42, values.iter().sum::<u32>());
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.write_sparse(10, &[(5usize, 1u32), (5, 2)], |w, v| w.write_u32(*v)),
+            Err(BufferError::InvalidSparseIndex { index: 5 })
+        ));
+        assert!(matches!(
+            buffer.write_sparse(10, &[(10usize, 1u32)], |w, v| w.write_u32(*v)),
+            Err(BufferError::InvalidSparseIndex { index: 10 })
+        ));
+        assert!(matches!(
+            buffer.write_sparse(i32::MAX as usize + 1, &[], |w, v: &u32| w.write_u32(*v)),
+            Err(BufferError::VecTooLong { .. })
+        ));
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_7bit_int(i32::MAX).unwrap();
+        buffer.write_7bit_int(0).unwrap();
+        let limited = ReaderConfig {
+            max_vec_length: Some(1024),
+            ..ReaderConfig::default()
+        };
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00], data);
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(matches!(
+            reader.read_sparse(1 << 20, |r| r.read_u64()),
+            Err(BufferError::VecTooLong {
+                length: 2147483647,
+                max: 1048576
+            })
+        ));
+        let mut reader = BufferReader::with_config(Cursor::new(data), limited);
+        assert!(matches!(
+            reader.read_sparse(1 << 20, |r| r.read_u32()),
+            Err(BufferError::VecTooLong {
+                length: 2147483647,
+                max: 1024
+            })
+        ));
+    }
+
+    #[test]
+    fn hex() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(0xDEADBEEF).unwrap();
+        buffer.write_string("hi".to_string()).unwrap();
+        let hex = buffer.to_hex().unwrap();
+        assert_eq!("efbeadde026869", hex);
+        let mut reader = BufferReader::from_hex(&hex).unwrap();
+        assert_eq!(0xDEADBEEF, reader.read_u32().unwrap());
+        assert_eq!("hi", reader.read_string().unwrap());
+        let mut reader = BufferReader::from_hex("EFBEADDE").unwrap();
+        assert_eq!(0xDEADBEEF, reader.read_u32().unwrap());
+
+        assert!(matches!(
+            BufferReader::from_hex("abc"),
+            Err(BufferError::InvalidHex)
+        ));
+        assert!(matches!(
+            BufferReader::from_hex("zz"),
+            Err(BufferError::InvalidHex)
+        ));
+        assert!(matches!(
+            BufferReader::from_hex("+f"),
+            Err(BufferError::InvalidHex)
+        ));
+        assert!(matches!(
+            BufferReader::from_hex("é"),
+            Err(BufferError::InvalidHex)
+        ));
+    }
+
+    #[test]
+    fn delta_u32() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(!buffer.write_delta_u32(100, 100).unwrap());
+        assert!(buffer.write_delta_u32(100, 250).unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![0, 1, 250, 0, 0, 0], data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(100, reader.read_delta_u32(100).unwrap());
+        assert_eq!(250, reader.read_delta_u32(100).unwrap());
+    }
+
+    #[test]
+    fn assert_aligned() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let mut reader = BufferReader::new(Cursor::new(vec![0u8; 16]));
+        reader.assert_aligned(4).unwrap();
+        reader.read_u8().unwrap();
+        assert!(matches!(
+            reader.assert_aligned(4),
+            Err(BufferError::Misaligned {
+                position: 1,
+                alignment: 4
+            })
+        ));
+        reader.read_bytes(7).unwrap();
+        reader.assert_aligned(4).unwrap();
+        reader.assert_aligned(8).unwrap();
+        assert!(reader.assert_aligned(0).is_err());
+    }
+
+    #[test]
+    fn optional_sentinel_u32() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer
+            .write_optional_sentinel_u32(Some(7), u32::MAX)
+            .unwrap();
+        buffer.write_optional_sentinel_u32(None, u32::MAX).unwrap();
+        assert!(matches!(
+            buffer.write_optional_sentinel_u32(Some(u32::MAX), u32::MAX),
+            Err(BufferError::SentinelCollision { value: u32::MAX })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![7, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF], data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(
+            Some(7),
+            reader.read_optional_sentinel_u32(u32::MAX).unwrap()
+        );
+        assert_eq!(None, reader.read_optional_sentinel_u32(u32::MAX).unwrap());
+    }
+
+    #[test]
+    fn measured() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u8(1).unwrap();
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        reader.read_u8().unwrap();
+        let ((number, text), consumed) = reader
+            .measured(|r| Ok((r.read_u32()?, r.read_string()?)))
+            .unwrap();
+        assert_eq!(9001, number);
+        assert_eq!("Hello World!", text);
+        assert_eq!(4 + 1 + 12, consumed);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, SeekOrigin};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer
+            .write_length_prefixed(|w| {
+                w.write_u32(9001)?;
+                w.write_string("body".to_string())
+            })
+            .unwrap();
+        buffer.write_u8(0xFF).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[9, 0, 0, 0], &data[..4]);
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        let (number, text) = reader
+            .read_length_prefixed(|r| Ok((r.read_u32()?, r.read_string()?)))
+            .unwrap();
+        assert_eq!(9001, number);
+        assert_eq!("body", text);
+        assert_eq!(0xFF, reader.read_u8().unwrap());
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert!(matches!(
+            reader.read_length_prefixed(|r| r.read_u32()),
+            Err(BufferError::LengthMismatch {
+                declared: 9,
+                actual: 4
+            })
+        ));
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(1).unwrap();
+        assert!(matches!(
+            buffer.write_length_prefixed(|w| w.seek(0, SeekOrigin::Begin)),
+            Err(BufferError::IndexOutOfRange { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn nesting_depth() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig, SeekOrigin};
+        use std::io::Cursor;
+        // Each node is a child count followed by its children.
+        fn read_node(reader: &mut BufferReader<Cursor<Vec<u8>>>) -> Result<u32, BufferError> {
+            reader.read_nested(|r| {
+                let mut depth = 0;
+                for _ in 0..r.read_u8()? {
+                    depth = depth.max(read_node(r)?);
+                }
+                Ok(depth + 1)
+            })
+        }
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for _ in 0..199 {
+            buffer.write_u8(1).unwrap();
+        }
+        buffer.write_u8(0).unwrap();
+        let data = buffer.to_vec().unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(matches!(
+            read_node(&mut reader),
+            Err(BufferError::DepthExceeded { max_depth: 128 })
+        ));
+        reader.seek(0, SeekOrigin::Begin).unwrap();
+        reader.config.max_depth = 200;
+        assert_eq!(200, read_node(&mut reader).unwrap());
+
+        let config = ReaderConfig {
+            max_depth: 500,
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data), config);
+        assert_eq!(200, read_node(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn vec_u32be() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_vec_u32be(&[1, 2, 3]).unwrap();
+        buffer.write_vec_u32be(&[]).unwrap();
+        buffer.write_vec_u32be(&[0xAA; 300]).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(
+            &[0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 0, 0, 1, 0x2C],
+            &data[..15]
+        );
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert_eq!(vec![1, 2, 3], reader.read_vec_u32be().unwrap());
+        assert!(reader.read_vec_u32be().unwrap().is_empty());
+        assert_eq!(vec![0xAA; 300], reader.read_vec_u32be().unwrap());
+
+        let config = ReaderConfig {
+            max_vec_length: Some(16),
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data), config);
+        reader.read_vec_u32be().unwrap();
+        reader.read_vec_u32be().unwrap();
+        assert!(matches!(
+            reader.read_vec_u32be(),
+            Err(BufferError::VecTooLong {
+                length: 300,
+                max: 16
+            })
+        ));
+    }
+
+    #[test]
+    fn fork() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(1).unwrap();
+        buffer.write_u32(0x0302_0100).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(1, reader.read_u32().unwrap());
+
+        let mut fork = reader.fork().unwrap();
+        assert_eq!(4, fork.position().unwrap());
+        assert_eq!(0x0302_0100, reader.read_u32().unwrap());
+        assert_eq!(0, fork.read_u8().unwrap());
+        assert_eq!(1, fork.read_u8().unwrap());
+        assert_eq!(8, reader.position().unwrap());
+        assert_eq!(6, fork.position().unwrap());
+
+        fork.get_mut().get_mut()[0] = 0xFF;
+        assert_eq!(1, reader.read_bytes_at(0, 1).unwrap()[0]);
+    }
+
+    #[test]
+    fn bytes_rle() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut runs = vec![0u8; 1000];
+        runs.extend_from_slice(&[1, 2, 2, 3]);
+        runs.extend(vec![7u8; 500]);
+        let mut seed = 12345u32;
+        let random: Vec<u8> = (0..1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_bytes_rle(&runs).unwrap();
+        let runs_size = buffer.len().unwrap();
+        assert!(runs_size < 16);
+        buffer.write_bytes_rle(&random).unwrap();
+        let random_size = buffer.len().unwrap() - runs_size;
+        assert!(random_size <= 1000 + 5);
+        buffer.write_bytes_rle(&[]).unwrap();
+
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert_eq!(runs, reader.read_bytes_rle(runs.len()).unwrap());
+        assert_eq!(random, reader.read_bytes_rle(random.len()).unwrap());
+        assert!(reader.read_bytes_rle(0).unwrap().is_empty());
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert!(matches!(
+            reader.read_bytes_rle(10),
+            Err(BufferError::LengthMismatch {
+                declared: 10,
+                actual: 1000
+            })
+        ));
+    }
+
+    #[test]
+    fn enum_mapped() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Message {
+            Hello,
+            Data,
+            Goodbye,
+        }
+        const TABLE: &[(u32, Message)] = &[
+            (0x10, Message::Hello),
+            (0x200, Message::Data),
+            (0xFFFF, Message::Goodbye),
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_enum_mapped(Message::Data, TABLE).unwrap();
+        buffer.write_enum_mapped(Message::Goodbye, TABLE).unwrap();
+        buffer.write_u32(0x11).unwrap();
+        assert!(matches!(
+            buffer.write_enum_mapped(Message::Hello, &TABLE[1..]),
+            Err(BufferError::UnmappedValue)
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x00, 0x02, 0x00, 0x00], &data[..4]);
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(Message::Data, reader.read_enum_mapped(TABLE).unwrap());
+        assert_eq!(Message::Goodbye, reader.read_enum_mapped(TABLE).unwrap());
+        assert!(matches!(
+            reader.read_enum_mapped(TABLE),
+            Err(BufferError::InvalidTag { tag: 0x11 })
+        ));
+    }
+
+    #[test]
+    fn string_min_width() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(8, buffer.write_string_min_width("abc", 8, b' ').unwrap());
+        assert_eq!(4, buffer.write_string_min_width("wxyz", 4, b' ').unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(b"abc     wxyz".to_vec(), data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!("abc", reader.read_string_trimmed(8, b' ').unwrap());
+        assert_eq!("wxyz", reader.read_string_trimmed(4, b' ').unwrap());
+    }
+
+    #[test]
+    fn der_length() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(1, buffer.write_der_length(0x7F).unwrap());
+        assert_eq!(3, buffer.write_der_length(0x1234).unwrap());
+        assert_eq!(2, buffer.write_der_length(0x80).unwrap());
+        assert_eq!(9, buffer.write_der_length(u64::MAX).unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x7F, 0x82, 0x12, 0x34, 0x81, 0x80], &data[..6]);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(0x7F, reader.read_der_length().unwrap());
+        assert_eq!(0x1234, reader.read_der_length().unwrap());
+        assert_eq!(0x80, reader.read_der_length().unwrap());
+        assert_eq!(u64::MAX, reader.read_der_length().unwrap());
+
+        for invalid in [
+            vec![0x80],
+            vec![0x81, 0x05],
+            vec![0x82, 0x00, 0x90],
+            vec![0x89],
+        ] {
+            let mut reader = BufferReader::new(Cursor::new(invalid));
+            assert!(matches!(
+                reader.read_der_length(),
+                Err(BufferError::InvalidDerLength)
+            ));
+        }
+    }
+
+    #[test]
+    fn read_record() {
+        use crate::buffer::{BufferReader, Endianness, RecordField, RecordType, RecordValue};
+        use std::io::Cursor;
+        const HEADER: &[RecordField] = &[
+            RecordField::with_endianness(RecordType::U32, Endianness::Big),
+            RecordField::new(RecordType::U16),
+            RecordField::new(RecordType::I8),
+            RecordField::new(RecordType::F32),
+            RecordField::new(RecordType::Bytes(3)),
+        ];
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x02, 0x01, 0xFE];
+        data.extend_from_slice(&1.5f32.to_le_bytes());
+        data.extend_from_slice(b"abc");
+        let expected = vec![
+            RecordValue::U32(u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+            RecordValue::U16(u16::from_le_bytes([data[4], data[5]])),
+            RecordValue::I8(data[6] as i8),
+            RecordValue::F32(1.5),
+            RecordValue::Bytes(b"abc".to_vec()),
+        ];
+        let mut reader = BufferReader::new(Cursor::new(data));
+        let values = reader.read_record(HEADER).unwrap();
+        assert_eq!(expected, values);
+        assert_eq!(RecordValue::U16(0x0102), values[1]);
+        assert_eq!(RecordValue::I8(-2), values[2]);
+        assert!(reader.read_record(HEADER).is_err());
+    }
+
+    #[test]
+    fn checksum8() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, Checksum8, SeekOrigin};
+        use std::io::Cursor;
+        for algorithm in [Checksum8::Sum, Checksum8::Xor] {
+            let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+            buffer.write_u8(0xAA).unwrap();
+            buffer
+                .write_with_checksum8(algorithm, |w| {
+                    w.write_u32(0x0403_0201)?;
+                    w.write_u8(0xF0)
+                })
+                .unwrap();
+            let data = buffer.to_vec().unwrap();
+            let expected = match algorithm {
+                Checksum8::Sum => 0xFA,
+                Checksum8::Xor => 0xF4,
+            };
+            assert_eq!(vec![0xAA, 1, 2, 3, 4, 0xF0, expected], data);
+
+            let mut reader = BufferReader::new(Cursor::new(data.clone()));
+            reader.read_u8().unwrap();
+            let value = reader
+                .read_with_checksum8(algorithm, |r| Ok((r.read_u32()?, r.read_u8()?)))
+                .unwrap();
+            assert_eq!((0x0403_0201, 0xF0), value);
+
+            let mut corrupt = data;
+            corrupt[2] ^= 0x10;
+            let mut reader = BufferReader::new(Cursor::new(corrupt));
+            reader.read_u8().unwrap();
+            assert!(matches!(
+                reader.read_with_checksum8(algorithm, |r| Ok((r.read_u32()?, r.read_u8()?))),
+                Err(BufferError::ChecksumMismatch { .. })
+            ));
+        }
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(1).unwrap();
+        assert!(matches!(
+            buffer.write_with_checksum8(Checksum8::Sum, |w| w.seek(0, SeekOrigin::Begin)),
+            Err(BufferError::IndexOutOfRange { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn labels() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u8(0xAB).unwrap();
+        assert_eq!(1, buffer.mark_label("size").unwrap());
+        buffer.write_u32(0).unwrap();
+        buffer.write_string("body".to_string()).unwrap();
+        buffer.write_u32(9001).unwrap();
+        let size = buffer.len().unwrap() as u32;
+        buffer.patch_label_u32("size", size).unwrap();
+        assert_eq!(size as u64, buffer.position().unwrap());
+        assert!(matches!(
+            buffer.patch_label_u32("missing", 0),
+            Err(BufferError::UnknownLabel { .. })
+        ));
+
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(0xAB, reader.read_u8().unwrap());
+        assert_eq!(14, reader.read_u32().unwrap());
+        assert_eq!("body", reader.read_string().unwrap());
+        assert_eq!(9001, reader.read_u32().unwrap());
+    }
+
+    #[test]
+    fn fixed_size_records() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let data: Vec<u8> = (0..10u8).flat_map(|i| vec![i; 16]).collect();
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(10, reader.record_count(16).unwrap());
+        assert_eq!(vec![2u8; 16], reader.record_at(16, 2).unwrap());
+        assert_eq!(0, reader.position().unwrap());
+        assert!(matches!(
+            reader.record_at(16, 10),
+            Err(BufferError::EndOfStream {
+                needed: 16,
+                available: 0
+            })
+        ));
+        assert!(matches!(
+            reader.record_at(16, u64::MAX),
+            Err(BufferError::EndOfStream {
+                needed: 16,
+                available: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn optional_fields() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        type WriteField = dyn Fn(&mut BufferWriter<Cursor<Vec<u8>>>) -> Result<u64, BufferError>;
+        type ReadField = dyn Fn(&mut BufferReader<Cursor<Vec<u8>>>) -> Result<u32, BufferError>;
+        let writers: [(u32, &WriteField); 4] = [
+            (0, &|w| w.write_u32(100)),
+            (1, &|w| w.write_u32(101)),
+            (2, &|w| w.write_u32(102)),
+            (3, &|w| w.write_u32(103)),
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_optional_fields(0b0101, &writers).unwrap();
+        assert!(matches!(
+            buffer.write_optional_fields(0b1_0000, &writers),
+            Err(BufferError::UnexpectedFields { mask: 0b1_0000 })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(12, data.len());
+
+        let readers: [(u32, &ReadField); 4] = [
+            (0, &|r| r.read_u32()),
+            (1, &|r| r.read_u32()),
+            (2, &|r| r.read_u32()),
+            (3, &|r| r.read_u32()),
+        ];
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert_eq!(
+            vec![Some(100), None, Some(102), None],
+            reader.read_optional_fields(&readers).unwrap()
+        );
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert!(matches!(
+            reader.read_optional_fields(&readers[1..]),
+            Err(BufferError::UnexpectedFields { mask: 0b1 })
+        ));
+    }
+
+    #[test]
+    fn self_length() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let buffer = |declared: u32| {
+            let mut data = declared.to_le_bytes().to_vec();
+            data.extend_from_slice(&[0u8; 12]);
+            BufferReader::new(Cursor::new(data))
+        };
+        buffer(16).validate_self_length_u32(true).unwrap();
+        buffer(12).validate_self_length_u32(false).unwrap();
+        assert!(matches!(
+            buffer(10).validate_self_length_u32(true),
+            Err(BufferError::LengthMismatch {
+                declared: 10,
+                actual: 16
+            })
+        ));
+        assert!(matches!(
+            buffer(20).validate_self_length_u32(false),
+            Err(BufferError::LengthMismatch {
+                declared: 20,
+                actual: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn u64_minimal() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(1, buffer.write_u64_minimal(0).unwrap());
+        assert_eq!(2, buffer.write_u64_minimal(255).unwrap());
+        assert_eq!(3, buffer.write_u64_minimal(256).unwrap());
+        assert_eq!(9, buffer.write_u64_minimal(u64::MAX).unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0, 1, 0xFF, 2, 0x00, 0x01, 8], &data[..7]);
+        assert_eq!(15, data.len());
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(0, reader.read_u64_minimal().unwrap());
+        assert_eq!(255, reader.read_u64_minimal().unwrap());
+        assert_eq!(256, reader.read_u64_minimal().unwrap());
+        assert_eq!(u64::MAX, reader.read_u64_minimal().unwrap());
+
+        let mut reader = BufferReader::new(Cursor::new(vec![9u8; 10]));
+        assert!(matches!(
+            reader.read_u64_minimal(),
+            Err(BufferError::InvalidIntegerLength { length: 9 })
+        ));
+    }
+
+    #[test]
+    fn tagged_union() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_union(1, |w| w.write_u32(9001)).unwrap();
+        buffer
+            .write_union(9, |w| w.write_string("from the future".to_string()))
+            .unwrap();
+        buffer.write_union(1, |w| w.write_u32(9002)).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[1, 4, 0x29, 0x23, 0, 0, 9, 16], &data[..8]);
+
+        type ReadVariant = dyn Fn(&mut BufferReader<Cursor<Vec<u8>>>) -> Result<u32, BufferError>;
+        let known: [(u8, &ReadVariant); 1] = [(1, &|r| r.read_u32())];
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(Some(9001), reader.read_union(&known).unwrap());
+        assert_eq!(None, reader.read_union(&known).unwrap());
+        assert_eq!(Some(9002), reader.read_union(&known).unwrap());
+        assert!(reader.read_union(&known).is_err());
+    }
+
+    #[test]
+    fn coord_e7() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, CoordinateAxis};
+        use std::io::Cursor;
+        let coords = [
+            (47.6062095, CoordinateAxis::Latitude),
+            (-122.3320708, CoordinateAxis::Longitude),
+            (-90.0, CoordinateAxis::Latitude),
+            (180.0, CoordinateAxis::Longitude),
+            (0.00000004, CoordinateAxis::Latitude),
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for (degrees, axis) in coords {
+            buffer.write_coord_e7(degrees, axis).unwrap();
+        }
+        assert!(matches!(
+            buffer.write_coord_e7(90.5, CoordinateAxis::Latitude),
+            Err(BufferError::CoordinateOutOfRange { .. })
+        ));
+        assert!(buffer
+            .write_coord_e7(f64::NAN, CoordinateAxis::Longitude)
+            .is_err());
+        buffer.write_i32(1_000_000_000).unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        for (degrees, axis) in coords {
+            let decoded = reader.read_coord_e7(axis).unwrap();
+            assert!((decoded - degrees).abs() <= 0.5e-7);
+        }
+        assert!(matches!(
+            reader.read_coord_e7(CoordinateAxis::Latitude),
+            Err(BufferError::CoordinateOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn deferred_region() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(1).unwrap();
+        buffer.write_7bit_int(8).unwrap();
+        buffer.write_u32(0xAAAA).unwrap();
+        buffer.write_u32(0xBBBB).unwrap();
+        buffer.write_u32(2).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(1, reader.read_u32().unwrap());
+        let region = reader.read_deferred().unwrap();
+        assert_eq!(5, region.offset);
+        assert_eq!(8, region.length);
+        assert_eq!(2, reader.read_u32().unwrap());
+
+        let (a, b) = reader
+            .decode_deferred(region, |r| Ok((r.read_u32()?, r.read_u32()?)))
+            .unwrap();
+        assert_eq!((0xAAAA, 0xBBBB), (a, b));
+        assert_eq!(17, reader.position().unwrap());
+        assert!(matches!(
+            reader.decode_deferred(region, |r| r.read_bytes(12)),
+            Err(BufferError::LengthMismatch {
+                declared: 8,
+                actual: 12
+            })
+        ));
+        assert_eq!(17, reader.position().unwrap());
+    }
+
+    #[test]
+    fn coverage_report() {
+        use crate::buffer::{BufferReader, ReaderConfig};
+        use std::io::Cursor;
+        let config = ReaderConfig {
+            track_coverage: true,
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(vec![0u8; 16]), config);
+        reader.read_u32().unwrap();
+        reader.read_bytes(4).unwrap();
+        reader.read_bytes_at(6, 4).unwrap();
+        reader.read_bytes_at(12, 2).unwrap();
+        let report = reader.coverage_report().unwrap();
+        assert_eq!(vec![10..12, 14..16], report.gaps);
+        assert_eq!(vec![6..8], report.overlaps);
+        assert!(!report.is_complete());
+
+        let mut reader = BufferReader::with_config(Cursor::new(vec![0u8; 8]), config);
+        reader.read_u64().unwrap();
+        assert!(reader.coverage_report().unwrap().is_complete());
+    }
+
+    #[test]
+    fn matrix_f32() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, MatrixOrder};
+        use std::io::Cursor;
+        let data: Vec<f32> = (0..12).map(|i| i as f32 * 0.5).collect();
+        for order in [MatrixOrder::RowMajor, MatrixOrder::ColumnMajor] {
+            let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+            buffer.write_matrix_f32(3, 4, &data, order).unwrap();
+            let bytes = buffer.to_vec().unwrap();
+            assert_eq!(2 + 12 * 4, bytes.len());
+            // The second element on the wire is (0, 1) for row-major and (1, 0) for column-major.
+            let second = f32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+            match order {
+                MatrixOrder::RowMajor => assert_eq!(data[1], second),
+                MatrixOrder::ColumnMajor => assert_eq!(data[4], second),
+            }
+            let mut reader = BufferReader::new(Cursor::new(bytes));
+            assert_eq!((3, 4, data.clone()), reader.read_matrix_f32(order).unwrap());
+        }
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.write_matrix_f32(3, 3, &data, MatrixOrder::RowMajor),
+            Err(BufferError::LayoutMismatch {
+                expected: 9,
+                actual: 12
+            })
+        ));
+        assert!(matches!(
+            buffer.write_matrix_f32(3_000_000_000, 0, &[], MatrixOrder::RowMajor),
+            Err(BufferError::VecTooLong {
+                length: 3_000_000_000,
+                max: 2147483647
+            })
+        ));
+        assert_eq!(0, buffer.len().unwrap());
+    }
+
+    #[test]
+    fn enum_hybrid() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_enum_hybrid(3, "Paused").unwrap();
+        buffer.write_enum_hybrid(0, "").unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(
+            (3, "Paused".to_string()),
+            reader.read_enum_hybrid().unwrap()
+        );
+        assert_eq!((0, String::new()), reader.read_enum_hybrid().unwrap());
+    }
+
+    #[test]
+    fn timestamp_quantized() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let start = 1_600_000_000_000_000_000i64;
+        let timestamps: Vec<i64> = (0..100)
+            .map(|i| start + i * 16_666_667 + (i * 7919) % 1_000_000)
+            .collect();
+        let mut sizes = Vec::new();
+        for quantum in [1_000i64, 1_000_000] {
+            let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+            for &t in &timestamps {
+                buffer.write_timestamp_quantized(t, quantum).unwrap();
+            }
+            let data = buffer.to_vec().unwrap();
+            sizes.push(data.len());
+            let mut reader = BufferReader::new(Cursor::new(data));
+            for &t in &timestamps {
+                let decoded = reader.read_timestamp_quantized(quantum).unwrap();
+                assert!((decoded - t).abs() <= quantum / 2);
+                assert_eq!(0, decoded % quantum);
+            }
+        }
+        assert!(sizes[1] < sizes[0]);
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.write_timestamp_quantized(0, 0),
+            Err(BufferError::InvalidQuantum { quantum_ns: 0 })
+        ));
+    }
+
+    #[test]
+    fn interned_strings() {
+        use crate::buffer::{
+            BufferError, BufferReader, BufferWriter, StringTableReader, StringTableWriter,
+        };
+        use std::io::Cursor;
+        let words = ["alpha", "beta", "alpha", "alpha", "gamma", "beta"];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        let mut table = StringTableWriter::new();
+        let mut sizes = Vec::new();
+        for word in words {
+            let start = buffer.position().unwrap();
+            table.write_interned(&mut buffer, word).unwrap();
+            buffer.write_u8(0xEE).unwrap();
+            sizes.push(buffer.position().unwrap() - start - 1);
+        }
+        assert_eq!(vec![7, 6, 1, 1, 7, 1], sizes);
+
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        let mut table = StringTableReader::new();
+        for word in words {
+            assert_eq!(word, table.read_interned(&mut reader).unwrap());
+            assert_eq!(0xEE, reader.read_u8().unwrap());
+        }
+
+        let mut reader = BufferReader::new(Cursor::new(vec![5]));
+        assert!(matches!(
+            StringTableReader::new().read_interned(&mut reader),
+            Err(BufferError::UnknownStringIndex { index: 4 })
+        ));
+    }
+
+    #[test]
+    fn block_adler32() {
+        use crate::buffer::{adler32, BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        assert_eq!(0x11E6_0398, adler32(b"Wikipedia"));
+        assert_eq!(1, adler32(&[]));
+        let large = vec![0xFFu8; 100_000];
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in &large {
+            a = (a + byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(((b << 16) | a) as u32, adler32(&large));
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(13, buffer.write_block_adler32(b"Wikipedia").unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x98, 0x03, 0xE6, 0x11], &data[9..]);
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert_eq!(b"Wikipedia".to_vec(), reader.read_block_adler32(9).unwrap());
+
+        let mut corrupt = data;
+        corrupt[0] = b'w';
+        let mut reader = BufferReader::new(Cursor::new(corrupt));
+        assert!(matches!(
+            reader.read_block_adler32(9),
+            Err(BufferError::ChecksumMismatch {
+                expected: 0x11E6_0398,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn footer_directory() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        let mut fields = Vec::new();
+        for (id, body) in [(7u32, "seven"), (42, "forty-two"), (3, "three")] {
+            let offset = buffer.position().unwrap();
+            buffer.write_bytes(body.as_bytes()).unwrap();
+            fields.push((id, offset, body.len() as u64));
+        }
+        let directory = buffer.position().unwrap();
+        buffer.write_u32(fields.len() as u32).unwrap();
+        for (id, offset, length) in &fields {
+            buffer.write_u32(*id).unwrap();
+            buffer.write_u64(*offset).unwrap();
+            buffer.write_u64(*length).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        let entries = reader.read_directory(directory).unwrap();
+        assert_eq!(3, entries.len());
+        assert_eq!(0, reader.position().unwrap());
+        let entry = entries.iter().find(|e| e.id == 42).unwrap();
+        assert_eq!(
+            b"forty-two".to_vec(),
+            reader.read_bytes_at(entry.offset, entry.length).unwrap()
+        );
+
+        let mut truncated = data;
+        truncated.truncate(truncated.len() - 1);
+        let mut reader = BufferReader::new(Cursor::new(truncated));
+        assert!(matches!(
+            reader.read_directory(directory),
+            Err(BufferError::EndOfStream { .. })
+        ));
+        assert_eq!(0, reader.position().unwrap());
+    }
+
+    #[test]
+    fn flags() {
+        use crate::buffer::{
+            flag_is_set, set_flag, BufferError, BufferReader, BufferWriter, IntWidth,
+        };
+        use std::io::Cursor;
+        let small = set_flag(set_flag(0, 0, true), 6, true);
+        assert_eq!(0b0100_0001, small);
+        let large = set_flag(set_flag(small, 31, true), 0, false);
+        assert_eq!(0x8000_0040, large);
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(1, buffer.write_flags(small, IntWidth::U8).unwrap());
+        assert_eq!(4, buffer.write_flags(large, IntWidth::U32).unwrap());
+        assert!(matches!(
+            buffer.write_flags(0x100, IntWidth::U8),
+            Err(BufferError::ValueTooWide {
+                value: 0x100,
+                bits: 8
+            })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        let read_small = reader.read_flags(IntWidth::U8).unwrap();
+        assert_eq!(small, read_small);
+        assert!(flag_is_set(read_small, 0) && flag_is_set(read_small, 6));
+        assert!(!flag_is_set(read_small, 1));
+        let read_large = reader.read_flags(IntWidth::U32).unwrap();
+        assert_eq!(large, read_large);
+        assert!(flag_is_set(read_large, 31) && !flag_is_set(read_large, 0));
+        assert!(!flag_is_set(read_large, 40));
+    }
+
+    #[cfg(feature = "bitflags")]
+    #[test]
+    fn typed_bitflags() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, IntWidth};
+        use std::io::Cursor;
+        bitflags::bitflags! {
+            #[derive(Debug, PartialEq)]
+            struct Permissions: u32 {
+                const READ = 0b001;
+                const WRITE = 0b010;
+                const EXECUTE = 0b100;
+            }
+        }
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer
+            .write_bitflags(Permissions::READ | Permissions::EXECUTE, IntWidth::U8)
+            .unwrap();
+        buffer.write_u8(0b1001).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(
+            Permissions::READ | Permissions::EXECUTE,
+            reader.read_bitflags(IntWidth::U8).unwrap()
+        );
+        assert!(matches!(
+            reader.read_bitflags::<Permissions>(IntWidth::U8),
+            Err(BufferError::UnexpectedFields { mask: 0b1000 })
+        ));
+    }
+
+    #[test]
+    fn read_string_to() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        // Multi-byte characters guarantee some straddle the chunk boundaries.
+        let text: String = (0..20_000).map(|i| ['a', 'é', '€', '😀'][i % 4]).collect();
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_string(text.clone()).unwrap();
+        buffer.write_u8(0xEE).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        let mut sink = Vec::new();
+        assert_eq!(text.len() as u64, reader.read_string_to(&mut sink).unwrap());
+        assert_eq!(text.as_bytes(), &sink[..]);
+        assert_eq!(0xEE, reader.read_u8().unwrap());
+
+        let mut reader = BufferReader::new(Cursor::new(vec![3, b'a', 0xE2, 0x82]));
+        assert!(matches!(
+            reader.read_string_to(&mut Vec::new()),
+            Err(BufferError::IOFailure)
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![2, 0xFF, b'a']));
+        assert!(reader.read_string_to(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn envelope() {
+        use crate::buffer::{BufferReader, BufferWriter, Envelope, SeekOrigin};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_envelope(0xC0FFEE, 3, b"unknown").unwrap();
+        assert_eq!(15, buffer.write_envelope(7, 2, b"known").unwrap());
+        let data = buffer.to_vec().unwrap();
+
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        let envelope = reader.read_envelope().unwrap();
+        assert_eq!(0xC0FFEE, envelope.schema_id);
+        assert_eq!(3, envelope.version);
+        assert_eq!(7, envelope.length);
+        assert_eq!(b"unknown".to_vec(), envelope.payload);
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        let mut decoded = Vec::new();
+        while reader.position().unwrap() < reader.len().unwrap() {
+            let header = reader.read_envelope_header().unwrap();
+            if header.schema_id != 7 {
+                reader
+                    .seek(header.length as i64, SeekOrigin::Current)
+                    .unwrap();
+                continue;
+            }
+            decoded.push(Envelope {
+                schema_id: header.schema_id,
+                version: header.version,
+                length: header.length,
+                payload: reader.read_bytes(header.length as u64).unwrap(),
+            });
+        }
+        assert_eq!(1, decoded.len());
+        assert_eq!(b"known".to_vec(), decoded[0].payload);
+        assert_eq!(2, decoded[0].version);
+    }
+
+    #[test]
+    fn vectored_writes() {
+        use crate::buffer::{BufferWriter, WriteBatch};
+        use std::io::{Cursor, IoSlice, Read, Seek, SeekFrom, Write};
+        // Accepts at most three bytes per call, but spread across several slices.
+        struct Trickle {
+            inner: Cursor<Vec<u8>>,
+            calls: usize,
+        }
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.write_vectored(&[IoSlice::new(buf)])
+            }
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+                self.calls += 1;
+                let mut budget = 3;
+                for buf in bufs {
+                    let n = buf.len().min(budget);
+                    self.inner.write_all(&buf[..n])?;
+                    budget -= n;
+                }
+                Ok(3 - budget)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+        impl Seek for Trickle {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let mut buffer = BufferWriter::new(Trickle {
+            inner: Cursor::new(Vec::new()),
+            calls: 0,
+        });
+        assert_eq!(
+            8,
+            buffer
+                .write_vectored(&[b"ab", b"", b"cde", b"fgh"])
+                .unwrap()
+        );
+        assert_eq!(3, buffer.get_ref().calls);
+        let header = 9001u32.to_le_bytes();
+        let mut batch = WriteBatch::new();
+        batch.push(&header);
+        batch.push(b"body");
+        assert_eq!(8, batch.len());
+        assert_eq!(8, buffer.write_batch(batch).unwrap());
+        assert!(WriteBatch::new().is_empty());
+        assert_eq!(0, buffer.write_batch(WriteBatch::new()).unwrap());
+
+        let mut expected = b"abcdefgh".to_vec();
+        expected.extend_from_slice(&header);
+        expected.extend_from_slice(b"body");
+        assert_eq!(expected, buffer.to_vec().unwrap());
+    }
+
+    #[test]
+    fn normalized16() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let unorm = [0.0f32, 1.0, 0.5, 0.123_456, 0.999_99];
+        let snorm = [-1.0f32, 1.0, 0.0, -0.5, 0.333_33];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for v in unorm {
+            buffer.write_unorm16(v).unwrap();
+        }
+        for v in snorm {
+            buffer.write_snorm16(v).unwrap();
+        }
+        buffer.write_unorm16(1.5).unwrap();
+        buffer.write_unorm16(f32::NAN).unwrap();
+        buffer.write_snorm16(-7.0).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x00, 0x00, 0xFF, 0xFF], &data[..4]);
+
+        let mut reader = BufferReader::new(Cursor::new(data));
+        for v in unorm {
+            assert!((reader.read_unorm16().unwrap() - v).abs() <= 1.0 / 65535.0);
+        }
+        for v in snorm {
+            assert!((reader.read_snorm16().unwrap() - v).abs() <= 1.0 / 32767.0);
+        }
+        assert_eq!(1.0, reader.read_unorm16().unwrap());
+        assert_eq!(0.0, reader.read_unorm16().unwrap());
+        assert_eq!(-1.0, reader.read_snorm16().unwrap());
+    }
+
+    #[test]
+    fn rgba8() {
+        use crate::buffer::{BufferReader, BufferWriter, ChannelOrder};
+        use std::io::Cursor;
+        let orders = [
+            (ChannelOrder::Rgba, 0x1122_3344u32),
+            (ChannelOrder::Bgra, 0x3322_1144),
+            (ChannelOrder::Argb, 0x4411_2233),
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_rgba8(0x11, 0x22, 0x33, 0x44).unwrap();
+        for (order, _) in orders.iter() {
+            buffer
+                .write_rgba8_u32(0x11, 0x22, 0x33, 0x44, *order)
+                .unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x11, 0x22, 0x33, 0x44], &data[..4]);
+        for (i, (_, packed)) in orders.iter().enumerate() {
+            let start = 4 + i * 4;
+            assert_eq!(&packed.to_le_bytes(), &data[start..start + 4]);
+        }
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!((0x11, 0x22, 0x33, 0x44), reader.read_rgba8().unwrap());
+        for (order, _) in orders.iter() {
+            assert_eq!(
+                (0x11, 0x22, 0x33, 0x44),
+                reader.read_rgba8_u32(*order).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn quaternion_compressed() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let half = core::f32::consts::FRAC_1_SQRT_2;
+        let orientations = [
+            [0.0f32, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [half, 0.0, half, 0.0],
+            [-0.5, 0.5, -0.5, 0.5],
+            [0.1826, -0.3651, 0.5477, -0.7303],
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for q in orientations.iter() {
+            buffer.write_quaternion_compressed(*q).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(orientations.len() * 4, data.len());
+        let mut reader = BufferReader::new(Cursor::new(data));
+        for q in orientations.iter() {
+            let decoded = reader.read_quaternion_compressed().unwrap();
+            // The decoded quaternion may be negated, which is the same rotation.
+            let dot: f32 = q.iter().zip(decoded.iter()).map(|(a, b)| a * b).sum();
+            let sign = dot.signum();
+            let largest = (0..4)
+                .max_by(|&a, &b| q[a].abs().total_cmp(&q[b].abs()))
+                .unwrap();
+            for (i, (a, b)) in q.iter().zip(decoded.iter()).enumerate() {
+                let tolerance = if i == largest { 2e-3 } else { 7e-4 };
+                assert!((a - b * sign).abs() < tolerance, "{:?} vs {:?}", q, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_u64() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let values = [3u64, 7, 7, 200, 1 << 40, u64::MAX];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_sorted_u64(&values).unwrap();
+        buffer.write_sorted_u64(&[]).unwrap();
+        assert!(matches!(
+            buffer.write_sorted_u64(&[1, 5, 4]),
+            Err(BufferError::UnsortedSequence { index: 2 })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[6, 3, 4, 0, 0xC1, 0x01], &data[..6]);
+        assert_eq!(0, *data.last().unwrap());
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(values.to_vec(), reader.read_sorted_u64().unwrap());
+        assert!(reader.read_sorted_u64().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ranged() {
+        use crate::buffer::{BitReader, BitWriter, BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let cases = [
+            (5i64, 0i64, 7i64),
+            (-3, -10, 10),
+            (42, 42, 42),
+            (1000, 0, 1023),
+            (i64::MIN, i64::MIN, i64::MAX),
+            (-1, -1, 0),
+        ];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        let mut bits = BitWriter::new(&mut buffer);
+        for &(value, min, max) in cases.iter() {
+            bits.write_ranged(value, min, max).unwrap();
+        }
+        assert!(matches!(
+            bits.write_ranged(8, 0, 7),
+            Err(BufferError::ValueOutOfRange { value: 8, .. })
+        ));
+        bits.finish().unwrap();
+        let data = buffer.to_vec().unwrap();
+        // 3 + 5 + 0 + 10 + 64 + 1 bits round up to 11 bytes.
+        assert_eq!(11, data.len());
+        assert_eq!(0b101_00111, data[0]);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        let mut bits = BitReader::new(&mut reader);
+        for &(value, min, max) in cases.iter() {
+            assert_eq!(value, bits.read_ranged(min, max).unwrap());
+        }
+    }
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let id = uuid::Uuid::parse_str("00112233-4455-6677-8899-aabbccddeeff").unwrap();
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_uuid(id).unwrap();
+        buffer.write_uuid_le(id).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(
+            &[
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD,
+                0xEE, 0xFF
+            ],
+            &data[..16]
+        );
+        assert_eq!(
+            &[
+                0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD,
+                0xEE, 0xFF
+            ],
+            &data[16..]
+        );
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(id, reader.read_uuid().unwrap());
+        assert_eq!(id, reader.read_uuid_le().unwrap());
+    }
+
+    #[test]
+    fn chunks() {
+        use crate::buffer::{BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(
+            12,
+            buffer
+                .write_chunk(*b"fmt ", &[1, 2, 3, 4], Endianness::Little)
+                .unwrap()
+        );
+        assert_eq!(
+            12,
+            buffer
+                .write_chunk(*b"NAME", &[9, 8, 7], Endianness::Big)
+                .unwrap()
+        );
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(b"fmt \x04\x00\x00\x00\x01\x02\x03\x04", &data[..12]);
+        assert_eq!(b"NAME\x00\x00\x00\x03\x09\x08\x07\x00", &data[12..]);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        let (fourcc, payload) = reader.read_chunk(Endianness::Little).unwrap();
+        assert_eq!((b"fmt ".to_vec(), vec![1, 2, 3, 4]), (fourcc, payload));
+        let (fourcc, payload) = reader.read_chunk(Endianness::Big).unwrap();
+        assert_eq!((b"NAME".to_vec(), vec![9, 8, 7]), (fourcc, payload));
+        assert_eq!(24, reader.position().unwrap());
+    }
+
+    #[test]
+    fn read_u16_round_trip() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let values = [0u16, 0xFF00, 0x00FF, 0x1234, u16::MAX];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for &value in values.iter() {
+            buffer.write_u16(value).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data));
+        for &value in values.iter() {
+            assert_eq!(value, reader.read_u16().unwrap());
+        }
+    }
+
+    #[test]
+    fn endianness() {
+        use crate::buffer::{BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let mut little = BufferWriter::new(Cursor::new(Vec::new()));
+        little.write_u32(0x1122_3344).unwrap();
+        let mut big = BufferWriter::with_endianness(Cursor::new(Vec::new()), Endianness::Big);
+        big.write_u32(0x1122_3344).unwrap();
+        assert_eq!(vec![0x44, 0x33, 0x22, 0x11], little.to_vec().unwrap());
+        assert_eq!(vec![0x11, 0x22, 0x33, 0x44], big.to_vec().unwrap());
+
+        big.write_u16(0xABCD).unwrap();
+        big.write_u64(0x0102_0304_0506_0708).unwrap();
+        big.write_i32(-2).unwrap();
+        let data = big.to_vec().unwrap();
+        assert_eq!(&[0xAB, 0xCD, 0x01, 0x02], &data[4..8]);
+        assert_eq!(&[0xFF, 0xFF, 0xFF, 0xFE], &data[14..]);
+        let mut reader = BufferReader::with_endianness(Cursor::new(data), Endianness::Big);
+        assert_eq!(0x1122_3344, reader.read_u32().unwrap());
+        assert_eq!(0xABCD, reader.read_u16().unwrap());
+        assert_eq!(0x0102_0304_0506_0708, reader.read_u64().unwrap());
+        assert_eq!(-2, reader.read_i32().unwrap());
+    }
+
+    #[test]
+    fn short_writes() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+        struct OneByte {
+            inner: Cursor<Vec<u8>>,
+        }
+        impl Write for OneByte {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.inner.write(&buf[..buf.len().min(1)])
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Read for OneByte {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+        impl Seek for OneByte {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+        let mut buffer = BufferWriter::new(OneByte {
+            inner: Cursor::new(Vec::new()),
+        });
+        assert_eq!(2, buffer.write_u16(0x1234).unwrap());
+        assert_eq!(4, buffer.write_u32(0xDEAD_BEEF).unwrap());
+        assert_eq!(8, buffer.write_u64(u64::MAX - 1).unwrap());
+        assert_eq!(4, buffer.write_i32(-9001).unwrap());
+        assert_eq!(3, buffer.write_bytes(&[7, 8, 9]).unwrap());
+        assert_eq!(5, buffer.write_string("short".to_string()).unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(27, data.len());
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(0x1234, reader.read_u16().unwrap());
+        assert_eq!(0xDEAD_BEEF, reader.read_u32().unwrap());
+        assert_eq!(u64::MAX - 1, reader.read_u64().unwrap());
+        assert_eq!(-9001, reader.read_i32().unwrap());
+        assert_eq!(vec![7, 8, 9], reader.read_bytes(3).unwrap());
+        assert_eq!("short", reader.read_string().unwrap());
+    }
+
+    #[test]
+    fn signed_readers() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            buffer.write_u8(0xFF).unwrap();
+            buffer.write_u8(i8::MIN as u8).unwrap();
+            buffer.write_u16(-1i16 as u16).unwrap();
+            buffer.write_u16(i16::MIN as u16).unwrap();
+            buffer.write_u64(-1i64 as u64).unwrap();
+            buffer.write_u64(i64::MIN as u64).unwrap();
+            buffer.write_u8(0).unwrap();
+            let data = buffer.to_vec().unwrap();
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            assert_eq!(-1, reader.read_i8().unwrap());
+            assert_eq!(i8::MIN, reader.read_i8().unwrap());
+            assert_eq!(-1, reader.read_i16().unwrap());
+            assert_eq!(i16::MIN, reader.read_i16().unwrap());
+            assert_eq!(-1, reader.read_i64().unwrap());
+            assert_eq!(i64::MIN, reader.read_i64().unwrap());
+            assert!(matches!(
+                reader.read_i16(),
+                Err(BufferError::EndOfStream {
+                    needed: 2,
+                    available: 1
+                })
+            ));
+            assert!(matches!(
+                reader.read_i64(),
+                Err(BufferError::EndOfStream {
+                    needed: 8,
+                    available: 0
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn signed_writers() {
+        use crate::buffer::{BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            for &v in [-1, 0, i8::MIN, i8::MAX].iter() {
+                assert_eq!(1, buffer.write_i8(v).unwrap());
+            }
+            for &v in [-1, 0, i16::MIN, i16::MAX].iter() {
+                assert_eq!(2, buffer.write_i16(v).unwrap());
+            }
+            for &v in [-1, 0, i64::MIN, i64::MAX].iter() {
+                assert_eq!(8, buffer.write_i64(v).unwrap());
+            }
+            let data = buffer.to_vec().unwrap();
+            assert_eq!(4 + 8 + 32, data.len());
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            for &v in [-1, 0, i8::MIN, i8::MAX].iter() {
+                assert_eq!(v, reader.read_i8().unwrap());
+            }
+            for &v in [-1, 0, i16::MIN, i16::MAX].iter() {
+                assert_eq!(v, reader.read_i16().unwrap());
+            }
+            for &v in [-1, 0, i64::MIN, i64::MAX].iter() {
+                assert_eq!(v, reader.read_i64().unwrap());
+            }
+        }
+        let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), Endianness::Big);
+        buffer.write_i16(i16::MIN).unwrap();
+        assert_eq!(vec![0x80, 0x00], buffer.to_vec().unwrap());
+    }
+
+    #[test]
+    fn floats() {
+        use crate::buffer::{BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let singles = [
+            0.0f32,
+            -0.0,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            3.5,
+            -1.25e-7,
+        ];
+        let doubles = [0.0f64, -0.0, f64::INFINITY, std::f64::consts::PI, -6.02e23];
+        let payload_nan = f32::from_bits(0x7FC0_1234);
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            for &v in singles.iter() {
+                assert_eq!(4, buffer.write_f32(v).unwrap());
+            }
+            for &v in doubles.iter() {
+                assert_eq!(8, buffer.write_f64(v).unwrap());
+            }
+            buffer.write_f64(f64::NAN).unwrap();
+            buffer.write_f32(payload_nan).unwrap();
+            let data = buffer.to_vec().unwrap();
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            for &v in singles.iter() {
+                assert_eq!(v.to_bits(), reader.read_f32().unwrap().to_bits());
+            }
+            for &v in doubles.iter() {
+                assert_eq!(v.to_bits(), reader.read_f64().unwrap().to_bits());
+            }
+            assert!(reader.read_f64().unwrap().is_nan());
+            assert_eq!(0x7FC0_1234, reader.read_f32().unwrap().to_bits());
+        }
+    }
+
+    #[test]
+    fn bools() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_bool(true).unwrap();
+        buffer.write_bool(false).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![0x01, 0x00], data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert!(reader.read_bool().unwrap());
+        assert!(!reader.read_bool().unwrap());
+
+        let mut reader = BufferReader::new(Cursor::new(vec![0x02]));
+        assert!(reader.read_bool().unwrap());
+        let strict = ReaderConfig {
+            strict: true,
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(vec![0x02]), strict);
+        assert!(matches!(
+            reader.read_bool(),
+            Err(BufferError::InvalidBool { value: 2 })
+        ));
+    }
+
+    #[test]
+    fn plain_read_sources() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.write_7bit_int(300).unwrap();
+        buffer.write_bytes(&[1, 2, 3]).unwrap();
+        buffer.write_u8(7).unwrap();
+        let data = buffer.to_vec().unwrap();
+
+        // A byte slice can be read but neither seeked nor written.
+        let mut reader = BufferReader::new(&data[..]);
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+        assert_eq!(300, reader.read_7bit_int().unwrap());
+        assert_eq!(vec![1, 2, 3], reader.read_bytes(3).unwrap());
+        assert_eq!(7, reader.read_u8().unwrap());
+        assert!(matches!(
+            reader.read_u8(),
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            })
+        ));
+        assert!(matches!(
+            reader.read_u32(),
+            Err(BufferError::EndOfStream {
+                needed: 4,
+                available: 0
+            })
+        ));
+        assert!(matches!(
+            reader.read_bytes(2),
+            Err(BufferError::EndOfStream {
+                needed: 2,
+                available: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn write_only_sinks() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        // A bare Vec<u8> can be written to but not seeked.
+        let mut buffer = BufferWriter::new(Vec::new());
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.write_7bit_int(300).unwrap();
+        let data = buffer.into_inner();
+        assert_eq!(4 + 1 + 12 + 2, data.len());
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+        assert_eq!(300, reader.read_7bit_int().unwrap());
+
+        let mut sink = BufferWriter::new(std::io::sink());
+        assert_eq!(8, sink.write_u64(u64::MAX).unwrap());
+    }
+
+    #[test]
+    fn huge_string_length() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_7bit_int(i32::MAX).unwrap();
+        buffer.write_bytes(b"tiny").unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(matches!(
+            reader.read_string(),
+            Err(BufferError::EndOfStream {
+                needed: 2147483647,
+                available: 4
+            })
+        ));
+        let mut reader = BufferReader::new(&data[..]);
+        assert!(matches!(
+            reader.read_string(),
+            Err(BufferError::EndOfStream {
+                needed: 2147483647,
+                available: 4
+            })
+        ));
+
+        let long = vec![0x5A; 200_000];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_bytes(&long).unwrap();
+        let mut reader = BufferReader::new(Cursor::new(buffer.to_vec().unwrap()));
+        assert_eq!(long, reader.read_bytes(200_000).unwrap());
+    }
+
+    #[test]
+    fn seven_bit_long() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let values = [0i64, 127, 128, u32::MAX as i64, i64::MAX, -1, i64::MIN];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for &value in values.iter() {
+            buffer.write_7bit_long(value).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x00, 0x7F, 0x80, 0x01], &data[..4]);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        for &value in values.iter() {
+            assert_eq!(value, reader.read_7bit_long().unwrap());
+        }
+        let mut reader = BufferReader::new(Cursor::new(vec![0x80; 11]));
+        assert!(matches!(
+            reader.read_7bit_long(),
+            Err(BufferError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_7bit_int() {
+        use crate::buffer::{BufferError, BufferReader, ReaderConfig};
+        use std::io::Cursor;
+        let mut reader = BufferReader::new(Cursor::new(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x01]));
+        assert!(matches!(
+            reader.read_7bit_int(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0x80, 0x80, 0x80, 0x80, 0x08]));
+        assert!(matches!(
+            reader.read_7bit_int(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0x80, 0x80, 0x80, 0x80, 0x10]));
+        assert!(matches!(
+            reader.read_7bit_int(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let strict = ReaderConfig {
+            strict: true,
+            ..ReaderConfig::default()
+        };
+        let mut reader =
+            BufferReader::with_config(Cursor::new(vec![0x80, 0x80, 0x80, 0x80, 0x10]), strict);
+        assert!(matches!(
+            reader.read_7bit_int(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]));
+        assert_eq!(i32::MAX, reader.read_7bit_int().unwrap());
+
+        let mut overlong = vec![0x80; 9];
+        overlong.push(0x02);
+        let mut reader = BufferReader::new(Cursor::new(overlong));
+        assert!(matches!(
+            reader.read_7bit_long(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let mut max = vec![0xFF; 9];
+        max.push(0x01);
+        let mut reader = BufferReader::new(Cursor::new(max));
+        assert_eq!(-1, reader.read_7bit_long().unwrap());
+    }
+
+    #[test]
+    fn varint_zigzag() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_varint_zigzag(-1).unwrap();
+        assert_eq!(vec![0x01], buffer.to_vec().unwrap());
+        let values = [0i64, -1, 1, -64, 63, 64, i64::MIN, i64::MAX];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        for &value in values.iter() {
+            buffer.write_varint_zigzag(value).unwrap();
+        }
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(&[0x00, 0x01, 0x02, 0x7F, 0x7E, 0x80, 0x01], &data[..7]);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        for &value in values.iter() {
+            assert_eq!(value, reader.read_varint_zigzag().unwrap());
+        }
+    }
+
+    #[test]
+    fn peek_u8() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let mut reader = BufferReader::new(Cursor::new(vec![0x2A, 0x07]));
+        assert_eq!(0x2A, reader.peek_u8().unwrap());
+        assert_eq!(0x2A, reader.peek_u8().unwrap());
+        assert_eq!(0, reader.position().unwrap());
+        assert_eq!(0x2A, reader.read_u8().unwrap());
+        assert_eq!(1, reader.position().unwrap());
+        assert_eq!(0x07, reader.read_u8().unwrap());
+        assert!(matches!(
+            reader.peek_u8(),
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            })
+        ));
+        assert_eq!(2, reader.position().unwrap());
+    }
+
+    #[test]
+    fn remaining() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u16(7).unwrap();
+        buffer.write_string("abc".to_string()).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(10, reader.remaining().unwrap());
+        reader.read_u32().unwrap();
+        assert_eq!(6, reader.remaining().unwrap());
+        assert_eq!(4, reader.position().unwrap());
+        reader.read_u16().unwrap();
+        assert_eq!(4, reader.remaining().unwrap());
+        assert!(!reader.is_empty().unwrap());
+        reader.read_string().unwrap();
+        assert_eq!(0, reader.remaining().unwrap());
+        assert!(reader.is_empty().unwrap());
+    }
+
+    #[test]
+    fn read_to_end() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_bytes(&[1, 2, 3, 4, 5]).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!(vec![1, 2, 3, 4, 5], reader.read_to_end().unwrap());
+        assert!(reader.is_empty().unwrap());
+        assert!(reader.read_to_end().unwrap().is_empty());
+
+        let mut reader = BufferReader::new(&data[..]);
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!(vec![1, 2, 3, 4, 5], reader.read_to_end().unwrap());
+    }
+
+    #[test]
+    fn skip() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::Cursor;
+        let data = vec![1u8, 2, 3, 4, 5, 6];
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        reader.skip(2).unwrap();
+        assert_eq!(3, reader.read_u8().unwrap());
+        reader.skip(0).unwrap();
+        reader.skip(3).unwrap();
+        assert!(reader.is_empty().unwrap());
+        assert!(matches!(
+            reader.skip(1),
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            })
+        ));
+
+        let mut reader = BufferReader::new(&data[..]);
+        reader.skip(5).unwrap();
+        assert_eq!(6, reader.read_u8().unwrap());
+        let mut reader = BufferReader::new(&data[..]);
+        assert!(matches!(
+            reader.skip(7),
+            Err(BufferError::EndOfStream {
+                needed: 7,
+                available: 6
+            })
+        ));
+    }
+
+    #[test]
+    fn sequential_reads_do_not_seek() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+        struct CountingSeeks {
+            inner: Cursor<Vec<u8>>,
+            seeks: usize,
+        }
+        impl Read for CountingSeeks {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+        impl Seek for CountingSeeks {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.seeks += 1;
+                self.inner.seek(pos)
+            }
+        }
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u64(9002).unwrap();
+        buffer.write_u16(9003).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        let mut reader = BufferReader::new(CountingSeeks {
+            inner: Cursor::new(buffer.to_vec().unwrap()),
+            seeks: 0,
+        });
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!(9002, reader.read_u64().unwrap());
+        assert_eq!(9003, reader.read_u16().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+        assert_eq!(0, reader.get_ref().seeks);
+    }
+
+    #[test]
+    fn into_inner() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u8(7).unwrap();
+        let data = buffer.into_inner().into_inner();
+        assert_eq!(vec![0x29, 0x23, 0, 0, 7], data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!(9001, reader.read_u32().unwrap());
+        let cursor = reader.into_inner();
+        assert_eq!(4, cursor.position());
+    }
+
+    #[test]
+    fn inner_accessors() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u16(0x0102).unwrap();
+        assert_eq!(&vec![0x02, 0x01], buffer.get_ref().get_ref());
+        buffer.get_mut().get_mut().push(0x03);
+        assert_eq!(vec![0x02, 0x01, 0x03], buffer.to_vec().unwrap());
+
+        let mut reader = BufferReader::new(Cursor::new(vec![0x0A, 0x0B, 0x0C]));
+        assert_eq!(0x0A, reader.read_u8().unwrap());
+        assert_eq!(1, reader.get_ref().position());
+        reader.get_mut().set_position(2);
+        assert_eq!(0x0C, reader.read_u8().unwrap());
+    }
+
+    #[test]
+    fn write_bytes_from_slices() {
+        use crate::buffer::BufferWriter;
+        use std::io::Cursor;
+        let array = [1u8, 2, 3, 4];
+        let larger = vec![9u8, 8, 7, 6, 5];
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(4, buffer.write_bytes(&array).unwrap());
+        assert_eq!(3, buffer.write_bytes(&larger[1..4]).unwrap());
+        assert_eq!(5, buffer.write_bytes(&larger).unwrap());
+        assert_eq!(
+            vec![1, 2, 3, 4, 8, 7, 6, 9, 8, 7, 6, 5],
+            buffer.to_vec().unwrap()
+        );
+    }
+
+    #[test]
+    fn blobs() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let large: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(0, buffer.write_blob(&[]).unwrap());
+        assert_eq!(5000, buffer.write_blob(&large).unwrap());
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(1 + 2 + 5000, data.len());
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(reader.read_blob().unwrap().is_empty());
+        assert_eq!(large, reader.read_blob().unwrap());
+
+        let config = ReaderConfig {
+            max_vec_length: Some(1024),
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data), config);
+        reader.read_blob().unwrap();
+        assert!(matches!(
+            reader.read_blob(),
+            Err(BufferError::VecTooLong { length: 5000, .. })
+        ));
+        let mut reader = BufferReader::new(Cursor::new(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]));
+        assert!(matches!(
+            reader.read_blob(),
+            Err(BufferError::EndOfStream {
+                needed: 2147483647,
+                available: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn seek_bounds() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, SeekOrigin};
+        use std::io::Cursor;
+        let mut reader = BufferReader::new(Cursor::new(vec![1u8, 2, 3, 4]));
+        reader.read_u8().unwrap();
+        assert!(matches!(
+            reader.seek(-1, SeekOrigin::Begin),
+            Err(BufferError::IndexOutOfRange { index: -1 })
+        ));
+        assert_eq!(1, reader.position().unwrap());
+        assert!(matches!(
+            reader.read_bytes_at(u64::MAX, 10),
+            Err(BufferError::EndOfStream {
+                needed: 10,
+                available: 0
+            })
+        ));
+        assert_eq!(vec![3, 4], reader.read_bytes_at(2, 2).unwrap());
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            buffer.seek(-5, SeekOrigin::Begin),
+            Err(BufferError::IndexOutOfRange { index: -5 })
+        ));
+    }
+
+    #[test]
+    fn to_vec_errors() {
+        use crate::buffer::{BufferError, BufferWriter, SeekOrigin};
+        use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+        struct Unreadable(Cursor<Vec<u8>>);
+        impl Write for Unreadable {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Read for Unreadable {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("not readable"))
+            }
+        }
+        impl Seek for Unreadable {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+        let mut buffer = BufferWriter::new(Unreadable(Cursor::new(Vec::new())));
+        buffer.write_u32(9001).unwrap();
+        assert!(matches!(buffer.to_vec(), Err(BufferError::ReadFailure(_))));
+        assert_eq!(4, buffer.position().unwrap());
+
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.seek(2, SeekOrigin::Begin).unwrap();
+        assert_eq!(4, buffer.to_vec().unwrap().len());
+        assert_eq!(2, buffer.position().unwrap());
+    }
+
+    #[test]
+    fn flush() {
+        use crate::buffer::BufferWriter;
+        use std::io::Write;
+        #[derive(Default)]
+        struct RecordsFlush {
+            data: Vec<u8>,
+            flushed: bool,
+        }
+        impl Write for RecordsFlush {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.data.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushed = true;
+                Ok(())
+            }
+        }
+        let mut buffer = BufferWriter::new(RecordsFlush::default());
+        buffer.write_u32(9001).unwrap();
+        assert!(!buffer.get_ref().flushed);
+        buffer.flush().unwrap();
+        assert!(buffer.get_ref().flushed);
+        assert_eq!(4, buffer.get_ref().data.len());
+    }
+
+    #[test]
+    fn utf16_strings() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let text = "héllo 🦀";
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            // The crab needs a surrogate pair, so eight code units in all.
+            assert_eq!(16, buffer.write_string_utf16(text).unwrap());
+            buffer.write_string_utf16("").unwrap();
+            let data = buffer.to_vec().unwrap();
+            assert_eq!(8, data[0]);
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            assert_eq!(text, reader.read_string_utf16().unwrap());
+            assert_eq!("", reader.read_string_utf16().unwrap());
+        }
+        let mut reader = BufferReader::new(Cursor::new(vec![0x01, 0x00, 0xD8]));
+        assert!(matches!(
+            reader.read_string_utf16(),
+            Err(BufferError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn cstrings() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(0, buffer.write_cstring("").unwrap());
+        assert_eq!(5, buffer.write_cstring("hello").unwrap());
+        assert!(matches!(
+            buffer.write_cstring("nul\0inside"),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(b"\0hello\0".to_vec(), data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!("", reader.read_cstring().unwrap());
+        assert_eq!("hello", reader.read_cstring().unwrap());
+        assert!(matches!(
+            reader.read_cstring(),
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            })
+        ));
+        // A final string may run to the end of the stream without a terminator.
+        let mut reader = BufferReader::new(&b"tail"[..]);
+        assert_eq!("tail", reader.read_cstring().unwrap());
+    }
+
+    #[test]
+    fn fixed_strings() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        assert_eq!(4, buffer.write_fixed_string("full", 4, b' ').unwrap());
+        assert_eq!(2, buffer.write_fixed_string("ab", 6, b' ').unwrap());
+        assert_eq!(1, buffer.write_fixed_string("z", 3, 0).unwrap());
+        assert!(matches!(
+            buffer.write_fixed_string("too long", 4, b' '),
+            Err(BufferError::StringTooLong { length: 8, max: 4 })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(b"fullab    z\0\0".to_vec(), data);
+        let mut reader = BufferReader::new(Cursor::new(data));
+        assert_eq!("full", reader.read_fixed_string(4, b' ').unwrap());
+        assert_eq!("ab", reader.read_fixed_string(6, b' ').unwrap());
+        assert_eq!("z", reader.read_fixed_string(3, b' ').unwrap());
+        assert!(reader.is_empty().unwrap());
+    }
+
+    #[test]
+    fn primitive_arrays() {
+        use crate::buffer::{BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let values: Vec<u32> = (0..5000u32).map(|i| i.wrapping_mul(2654435761)).collect();
+        let floats = [1.5f64, -0.0, f64::MAX];
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            assert_eq!(0, buffer.write_u32_array(&[]).unwrap());
+            assert_eq!(20000, buffer.write_u32_array(&values).unwrap());
+            buffer.write_i16_array(&[-1, 2, i16::MIN]).unwrap();
+            buffer.write_f64_array(&floats).unwrap();
+            let data = buffer.to_vec().unwrap();
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            assert_eq!(Vec::<u32>::new(), reader.read_u32_array().unwrap());
+            assert_eq!(values, reader.read_u32_array().unwrap());
+            assert_eq!(vec![-1, 2, i16::MIN], reader.read_i16_array().unwrap());
+            assert_eq!(floats.to_vec(), reader.read_f64_array().unwrap());
+            assert!(reader.is_empty().unwrap());
+        }
+    }
+
+    #[test]
+    fn primitive_array_count_is_limited() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, ReaderConfig};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_7bit_int(i32::MAX).unwrap();
+        let data = buffer.to_vec().unwrap();
+        // A huge count with no elements behind it just runs out of stream.
+        let mut reader = BufferReader::new(Cursor::new(data.clone()));
+        assert!(matches!(
+            reader.read_u64_array(),
+            Err(BufferError::EndOfStream {
+                needed: 8,
+                available: 0
+            })
+        ));
+        let config = ReaderConfig {
+            max_vec_length: Some(1024),
+            ..ReaderConfig::default()
+        };
+        let mut reader = BufferReader::with_config(Cursor::new(data), config);
+        assert!(matches!(
+            reader.read_u64_array(),
+            Err(BufferError::VecTooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn read_integers_at() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), Endianness::Big);
+        buffer.write_u16(0xBEEF).unwrap();
+        buffer.write_u32(0xDEADBEEF).unwrap();
+        buffer.write_u64(42).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::with_endianness(Cursor::new(data), Endianness::Big);
+        assert_eq!(0xBEEF, reader.read_u16().unwrap());
+        assert_eq!(0xDEADBEEF, reader.read_u32_at(2).unwrap());
+        assert_eq!(42, reader.read_u64_at(6).unwrap());
+        assert_eq!(0xBEEF, reader.read_u16_at(0).unwrap());
+        assert!(matches!(
+            reader.read_u64_at(10),
+            Err(BufferError::EndOfStream {
+                needed: 8,
+                available: 4
+            })
+        ));
+        assert_eq!(2, reader.position().unwrap());
+        assert_eq!(0xDEADBEEF, reader.read_u32().unwrap());
+    }
+
+    #[test]
+    fn errors_compare_and_clone() {
+        use crate::buffer::{BufferError, BufferReader};
+        use std::io::{Cursor, Error, ErrorKind};
+        let mut reader = BufferReader::new(Cursor::new(vec![1u8]));
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 4,
+                available: 1
+            }),
+            reader.read_u32()
+        );
+        let errors = vec![
+            BufferError::EndOfStream {
+                needed: 4,
+                available: 1,
+            },
+            BufferError::IOFailure,
+            BufferError::ReadFailure(Error::other("disk on fire")),
+            BufferError::ReadFailure(Error::new(ErrorKind::Interrupted, "disk on fire")),
+            BufferError::InvalidData {
+                message: "bad".to_string(),
+            },
+            BufferError::InvalidData {
+                message: "worse".to_string(),
+            },
+            BufferError::StringTooLong { length: 8, max: 4 },
+            BufferError::StringTooLong { length: 8, max: 5 },
+            BufferError::ValueOutOfRange {
+                value: 1,
+                min: 2,
+                max: 3,
+            },
+            BufferError::CoordinateOutOfRange { degrees: 181.0 },
+        ];
+        for (i, a) in errors.iter().enumerate() {
+            assert_eq!(a, &a.clone());
+            for (j, b) in errors.iter().enumerate() {
+                assert_eq!(i == j, a == b, "{:?} vs {:?}", a, b);
+            }
+        }
+        // Read failures are compared by kind alone.
+        assert_eq!(
+            BufferError::ReadFailure(Error::other("one")),
+            BufferError::ReadFailure(Error::other("two"))
+        );
+        match errors[2].clone() {
+            BufferError::ReadFailure(error) => assert_eq!("disk on fire", error.to_string()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_from_bytes() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+        assert!(reader.is_empty().unwrap());
+        let mut reader = BufferReader::from_bytes(data);
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+    }
+
+    #[test]
+    fn writer_in_memory() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_u32(9001).unwrap();
+        buffer.write_bool(true).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(18, data.len());
+        let mut reader = BufferReader::from_bytes(data);
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert!(reader.read_bool().unwrap());
+        assert_eq!("Hello World!", reader.read_string().unwrap());
+    }
+
+    #[test]
+    fn serialize_traits() {
+        use crate::buffer::{
+            BufferDeserialize, BufferError, BufferReader, BufferSerialize, BufferWriter,
+        };
+        use std::io::{Read, Write};
+
+        #[derive(Debug, PartialEq)]
+        struct Player {
+            id: u32,
+            name: String,
+            health: f32,
+            alive: bool,
+            score: i64,
+        }
+
+        impl BufferSerialize for Player {
+            fn write_to<W: Write>(&self, writer: &mut BufferWriter<W>) -> Result<(), BufferError> {
+                self.id.write_to(writer)?;
+                self.name.write_to(writer)?;
+                self.health.write_to(writer)?;
+                self.alive.write_to(writer)?;
+                self.score.write_to(writer)
+            }
+        }
+
+        impl BufferDeserialize for Player {
+            fn read_from<R: Read>(reader: &mut BufferReader<R>) -> Result<Self, BufferError> {
+                Ok(Player {
+                    id: u32::read_from(reader)?,
+                    name: String::read_from(reader)?,
+                    health: f32::read_from(reader)?,
+                    alive: bool::read_from(reader)?,
+                    score: i64::read_from(reader)?,
+                })
+            }
+        }
+
+        let player = Player {
+            id: 7,
+            name: "ferris".to_string(),
+            health: 0.75,
+            alive: true,
+            score: -12,
+        };
+        let mut buffer = BufferWriter::new_in_memory();
+        player.write_to(&mut buffer).unwrap();
+        250u8.write_to(&mut buffer).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        assert_eq!(player, Player::read_from(&mut reader).unwrap());
+        assert_eq!(250, u8::read_from(&mut reader).unwrap());
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 4,
+                available: 0
+            }),
+            Player::read_from(&mut reader)
+        );
+    }
+
+    #[test]
+    fn trailing_checksum() {
+        use crate::buffer::{crc32, BufferError, BufferReader, BufferWriter};
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0, crc32(&[]));
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.write_checksum().unwrap();
+        let mut data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        reader.verify_checksum().unwrap();
+        assert_eq!(0, reader.position().unwrap());
+        assert_eq!(9001, reader.read_u32().unwrap());
+
+        data[6] ^= 0x01;
+        let mut reader = BufferReader::from_slice(&data);
+        assert!(matches!(
+            reader.verify_checksum(),
+            Err(BufferError::ChecksumMismatch { .. })
+        ));
+        let mut reader = BufferReader::from_slice(&[1, 2]);
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 4,
+                available: 2
+            }),
+            reader.verify_checksum()
+        );
+    }
+
+    #[test]
+    fn read_frame() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_bytes(&[1, 2, 3, 4]).unwrap();
+        buffer.write_u32(9001).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        let mut frame = reader.read_frame(4).unwrap();
+        assert_eq!(vec![1, 2, 3, 4], frame.read_bytes(4).unwrap());
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            }),
+            frame.read_u8()
+        );
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 1,
+                available: 0
+            }),
+            reader.read_frame(1).map(|_f| ())
+        );
+    }
+
+    #[test]
+    fn align_to() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_u8(7).unwrap();
+        buffer.align_to(4).unwrap();
+        assert_eq!(4, buffer.position().unwrap());
+        buffer.align_to(4).unwrap();
+        assert_eq!(4, buffer.position().unwrap());
+        buffer.write_u32(9001).unwrap();
+        assert!(matches!(
+            buffer.align_to(3),
+            Err(BufferError::InvalidData { .. })
+        ));
+        assert!(matches!(
+            buffer.align_to(0),
+            Err(BufferError::InvalidData { .. })
+        ));
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![7, 0, 0, 0], data[..4].to_vec());
+        let mut reader = BufferReader::from_slice(&data);
+        assert_eq!(7, reader.read_u8().unwrap());
+        reader.align_to(4).unwrap();
+        assert_eq!(9001, reader.read_u32().unwrap());
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 8,
+                available: 0
+            }),
+            reader.align_to(16)
+        );
+    }
+
+    #[test]
+    fn read_into() {
+        use crate::buffer::{BufferError, BufferReader};
+        let data: Vec<u8> = (0..40).collect();
+        let mut reader = BufferReader::from_slice(&data);
+        let mut buffer = [0u8; 16];
+        reader.read_into(&mut buffer).unwrap();
+        assert_eq!(data[..16], buffer);
+        reader.read_into(&mut buffer).unwrap();
+        assert_eq!(data[16..32], buffer);
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 16,
+                available: 8
+            }),
+            reader.read_into(&mut buffer)
+        );
+    }
+
+    #[test]
+    fn int128() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, Endianness};
+        use std::io::Cursor;
+        for &endianness in [Endianness::Little, Endianness::Big].iter() {
+            let mut buffer = BufferWriter::with_endianness(Cursor::new(Vec::new()), endianness);
+            assert_eq!(16, buffer.write_u128(u128::MAX).unwrap());
+            buffer.write_u128(0).unwrap();
+            buffer.write_u128(1).unwrap();
+            buffer.write_i128(i128::MIN).unwrap();
+            buffer.write_i128(-1).unwrap();
+            let data = buffer.to_vec().unwrap();
+            let one = match endianness {
+                Endianness::Little => data[32],
+                Endianness::Big => data[47],
+            };
+            assert_eq!(1, one);
+            let mut reader = BufferReader::with_endianness(Cursor::new(data), endianness);
+            assert_eq!(u128::MAX, reader.read_u128().unwrap());
+            assert_eq!(0, reader.read_u128().unwrap());
+            assert_eq!(1, reader.read_u128().unwrap());
+            assert_eq!(i128::MIN, reader.read_i128().unwrap());
+            assert_eq!(-1, reader.read_i128().unwrap());
+            assert_eq!(
+                Err(BufferError::EndOfStream {
+                    needed: 16,
+                    available: 0
+                }),
+                reader.read_u128()
+            );
+        }
+    }
+
+    #[test]
+    fn chars() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        for &ch in ['a', 'é', '€', '🦀'].iter() {
+            assert_eq!(4, buffer.write_char(ch).unwrap());
+        }
+        buffer.write_u32(0xD800).unwrap();
+        buffer.write_u32(0x11_0000).unwrap();
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        for &ch in ['a', 'é', '€', '🦀'].iter() {
+            assert_eq!(ch, reader.read_char().unwrap());
+        }
+        assert!(matches!(
+            reader.read_char(),
+            Err(BufferError::InvalidData { .. })
+        ));
+        assert!(matches!(
+            reader.read_char(),
+            Err(BufferError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn reset() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        use std::io::Cursor;
+        let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u32(9002).unwrap();
+        buffer.reset().unwrap();
+        assert_eq!(0, buffer.position().unwrap());
+        buffer.write_u32(9003).unwrap();
+        // The cursor is handed over still positioned after the overwritten value.
+        let mut reader = BufferReader::new(buffer.into_inner());
+        assert_eq!(9002, reader.read_u32().unwrap());
+        reader.reset().unwrap();
+        assert_eq!(0, reader.position().unwrap());
+        assert_eq!(9003, reader.read_u32().unwrap());
+        assert_eq!(9002, reader.read_u32().unwrap());
+    }
+
+    #[test]
+    fn string_prefixed() {
+        use crate::buffer::{BufferError, BufferReader, BufferWriter, StringPrefix};
+        let prefixes = [
+            (StringPrefix::SevenBit, 1),
+            (StringPrefix::U8, 1),
+            (StringPrefix::U16, 2),
+            (StringPrefix::U32, 4),
+        ];
+        for &(prefix, width) in prefixes.iter() {
+            let mut buffer = BufferWriter::new_in_memory();
+            assert_eq!(5, buffer.write_string_prefixed("hello", prefix).unwrap());
+            let data = buffer.to_vec().unwrap();
+            assert_eq!(width + 5, data.len());
+            assert_eq!(5, data[0]);
+            let mut reader = BufferReader::from_slice(&data);
+            assert_eq!("hello", reader.read_string_prefixed(prefix).unwrap());
+        }
+        let mut buffer = BufferWriter::new_in_memory();
+        assert!(matches!(
+            buffer.write_string_prefixed(&"x".repeat(256), StringPrefix::U8),
+            Err(BufferError::StringTooLong {
+                length: 256,
+                max: 255
+            })
+        ));
+        assert_eq!(0, buffer.position().unwrap());
+    }
+
+    #[test]
+    fn optional_values() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_optional_string(None).unwrap();
+        buffer.write_optional_string(Some("x")).unwrap();
+        buffer
+            .write_option(Some(9001), BufferWriter::write_u32)
+            .unwrap();
+        buffer.write_option(None, BufferWriter::write_u32).unwrap();
+        let data = buffer.to_vec().unwrap();
+        assert_eq!(vec![0, 1, 1, b'x'], data[..4].to_vec());
+        let mut reader = BufferReader::from_slice(&data);
+        assert_eq!(None, reader.read_optional_string().unwrap());
+        assert_eq!(
+            Some("x".to_string()),
+            reader.read_optional_string().unwrap()
+        );
+        assert_eq!(
+            Some(9001),
+            reader.read_option(BufferReader::read_u32).unwrap()
+        );
+        assert_eq!(None, reader.read_option(BufferReader::read_u32).unwrap());
+        assert!(reader.is_empty().unwrap());
+    }
+
+    #[test]
+    fn hex_dump() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer
+            .write_bytes(b"Hello World!\n\x00\xff binary")
+            .unwrap();
+        let expected = "\
+00000000  48 65 6c 6c 6f 20 57 6f  72 6c 64 21 0a 00 ff 20  |Hello World!... |
+00000010  62 69 6e 61 72 79                                 |binary|
+";
+        assert_eq!(expected, buffer.hex_dump().unwrap());
+        assert_eq!(22, buffer.position().unwrap());
+        let data = buffer.to_vec().unwrap();
+        let mut reader = BufferReader::from_slice(&data);
+        reader.skip(6).unwrap();
+        assert_eq!(expected, reader.hex_dump().unwrap());
+        assert_eq!(6, reader.position().unwrap());
+        assert_eq!("", BufferReader::from_slice(&[]).hex_dump().unwrap());
+    }
+
+    #[test]
+    fn bytes_written() {
+        use crate::buffer::{BufferWriter, SeekOrigin};
+        let mut buffer = BufferWriter::new_in_memory();
+        assert_eq!(0, buffer.bytes_written());
+        buffer.write_u32(9001).unwrap();
+        buffer.write_u8(1).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        buffer.write_vectored(&[b"ab", b"cde"]).unwrap();
+        assert_eq!(23, buffer.bytes_written());
+        buffer.seek(0, SeekOrigin::Begin).unwrap();
+        buffer.write_u32(9002).unwrap();
+        assert_eq!(27, buffer.bytes_written());
+        assert_eq!(23, buffer.len().unwrap());
+    }
+
+    #[test]
+    fn end_of_stream_detail() {
+        use crate::buffer::{BufferError, BufferReader};
+        let mut reader = BufferReader::from_slice(&[1, 2, 3]);
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 8,
+                available: 3
+            }),
+            reader.read_u64()
+        );
+        let data = vec![0u8; 100_000];
+        let mut reader = BufferReader::new(&data[..]);
+        reader.skip(10).unwrap();
+        // Chunked reads still report the whole count.
+        assert_eq!(
+            Err(BufferError::EndOfStream {
+                needed: 200_000,
+                available: 99_990
+            }),
+            reader.read_bytes(200_000)
+        );
+    }
+
+    #[test]
+    fn fork_at() {
+        use crate::buffer::{BufferReader, BufferWriter};
+        let mut buffer = BufferWriter::new_in_memory();
+        buffer.write_u32(8).unwrap();
+        buffer.write_u32(9001).unwrap();
+        buffer.write_string("Hello World!".to_string()).unwrap();
+        let mut reader = BufferReader::from_bytes(buffer.to_vec().unwrap());
+        let table = reader.read_u32().unwrap();
+        let mut fork = reader.fork_at(table as u64).unwrap();
+        assert_eq!("Hello World!", fork.read_string().unwrap());
+        assert!(fork.is_empty().unwrap());
+        assert_eq!(4, reader.position().unwrap());
+        assert_eq!(9001, reader.read_u32().unwrap());
     }
 }
@@ -0,0 +1,42 @@
+#![cfg(feature = "std")]
+
+use buffer_io::buffer::{BufferReader, BufferWriter};
+use buffer_io::decompress::DecompressReader;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Write};
+
+fn gzip_records(count: u32) -> Vec<u8> {
+    let mut buffer = BufferWriter::new(Cursor::new(Vec::new()));
+    for i in 0..count {
+        buffer.write_u32(i).unwrap();
+        buffer.write_string(format!("record {}", i)).unwrap();
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&buffer.to_vec().unwrap()).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn reads_from_gzip_stream() {
+    let compressed = gzip_records(2000);
+    let decoder = GzDecoder::new(Cursor::new(compressed));
+    let mut reader = BufferReader::new(DecompressReader::new(decoder));
+    for i in 0..2000 {
+        assert_eq!(i, reader.read_u32().unwrap());
+        assert_eq!(format!("record {}", i), reader.read_string().unwrap());
+    }
+    assert!(reader.read_u8().is_err());
+    let first = reader.read_bytes_at(0, 4).unwrap();
+    assert_eq!(vec![0, 0, 0, 0], first);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_constructor() {
+    let compressed = gzip_records(3);
+    let mut reader = BufferReader::new(DecompressReader::gzip(Cursor::new(compressed)));
+    assert_eq!(0, reader.read_u32().unwrap());
+    assert_eq!("record 0", reader.read_string().unwrap());
+}
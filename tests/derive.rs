@@ -0,0 +1,91 @@
+#![cfg(feature = "derive")]
+
+use buffer_io::buffer::{
+    BufferDeserialize, BufferError, BufferReader, BufferSerialize, BufferWriter,
+};
+
+#[derive(Debug, PartialEq, BufferSerialize, BufferDeserialize)]
+struct Position(f32, f32);
+
+#[derive(Debug, PartialEq, BufferSerialize, BufferDeserialize)]
+enum Team {
+    Red,
+    Blue,
+}
+
+#[derive(Debug, PartialEq, BufferSerialize, BufferDeserialize)]
+enum Opcode {
+    Ping = 7,
+    Pong,
+    Close = 0x100,
+}
+
+#[derive(Debug, PartialEq, BufferSerialize, BufferDeserialize)]
+struct Player {
+    name: String,
+    scores: Vec<u32>,
+    position: Position,
+    team: Team,
+}
+
+#[test]
+fn derives_nested_structs() {
+    let players = vec![
+        Player {
+            name: "ferris".to_string(),
+            scores: vec![3, 1, 4, 1, 5],
+            position: Position(1.5, -2.0),
+            team: Team::Blue,
+        },
+        Player {
+            name: String::new(),
+            scores: Vec::new(),
+            position: Position(0.0, 0.0),
+            team: Team::Red,
+        },
+    ];
+    let mut buffer = BufferWriter::new_in_memory();
+    players.write_to(&mut buffer).unwrap();
+    let data = buffer.to_vec().unwrap();
+    let mut reader = BufferReader::from_slice(&data);
+    assert_eq!(players, Vec::<Player>::read_from(&mut reader).unwrap());
+    assert!(reader.is_empty().unwrap());
+
+    // Fields are written in declaration order.
+    let mut reader = BufferReader::from_slice(&data);
+    assert_eq!(2, reader.read_7bit_int().unwrap());
+    assert_eq!("ferris", reader.read_string().unwrap());
+    assert_eq!(vec![3, 1, 4, 1, 5], reader.read_u32_array().unwrap());
+    assert_eq!(1.5, reader.read_f32().unwrap());
+    assert_eq!(-2.0, reader.read_f32().unwrap());
+    assert_eq!(1, reader.read_u32().unwrap());
+}
+
+#[test]
+fn rejects_unknown_enum_tags() {
+    let mut reader = BufferReader::from_slice(&[2, 0, 0, 0]);
+    assert_eq!(
+        Err(BufferError::InvalidTag { tag: 2 }),
+        Team::read_from(&mut reader)
+    );
+}
+
+#[test]
+fn writes_explicit_enum_discriminants() {
+    let mut buffer = BufferWriter::new_in_memory();
+    for opcode in [Opcode::Ping, Opcode::Pong, Opcode::Close] {
+        opcode.write_to(&mut buffer).unwrap();
+    }
+    let data = buffer.to_vec().unwrap();
+    assert_eq!(vec![7, 0, 0, 0, 8, 0, 0, 0, 0, 1, 0, 0], data);
+
+    let mut reader = BufferReader::from_slice(&data);
+    assert_eq!(Opcode::Ping, Opcode::read_from(&mut reader).unwrap());
+    assert_eq!(Opcode::Pong, Opcode::read_from(&mut reader).unwrap());
+    assert_eq!(Opcode::Close, Opcode::read_from(&mut reader).unwrap());
+    let mut reader = BufferReader::from_slice(&[0, 0, 0, 0]);
+    assert_eq!(
+        Err(BufferError::InvalidTag { tag: 0 }),
+        Opcode::read_from(&mut reader)
+    );
+}